@@ -0,0 +1,145 @@
+use std::time::{Duration, Instant};
+
+use simhash::hamming_distance;
+
+use crate::budget::Limits;
+use crate::checkpoint::LiteralState;
+use crate::portfolio::{self, Best, Search};
+use crate::problem::{self, Problem};
+use crate::strategies::Strategy;
+
+// slices are sized so every literal could get about this many before the
+// budget's gone
+const SLICES_PER_LITERAL: u32 = 4;
+// shorter than this and a slice is mostly thread startup
+const MIN_SLICE: Duration = Duration::from_millis(250);
+
+/// One literal (or function run) competing for a file's search time.
+pub struct Arm {
+    // the target's kind name, for checkpoints
+    kind: &'static str,
+    pub span: (u32, u32),
+    // for progress messages
+    label: String,
+    pub problem: Problem,
+    pub best: Best,
+    // time and evaluations spent on it so far, including before a resume
+    pub spent: (Duration, u64),
+    // guessed bytes saved by a slice, before it's had any
+    prior: f64,
+    saved: usize,
+    slices: u32,
+    // stopped for a reason other than its slice running out, e.g. patience,
+    // so more time won't help
+    retired: bool,
+}
+
+impl Arm {
+    pub fn new(kind: &'static str, span: (u32, u32), label: String, problem: Problem, best: Best, spent: (Duration, u64)) -> Arm {
+        let prior = expected_gain(&problem, best.size);
+        let retired = problem.units.len() < 2;
+        Arm { kind, span, label, problem, best, spent, prior, saved: 0, slices: 0, retired }
+    }
+
+    // Average bytes saved per slice, with the prior counted as one slice so
+    // a single unlucky slice doesn't write a literal off.
+    fn mean_gain(&self) -> f64 {
+        (self.prior + self.saved as f64) / (self.slices + 1) as f64
+    }
+
+    pub fn state(&self, finished: bool) -> LiteralState {
+        LiteralState {
+            kind: self.kind.to_string(),
+            span: self.span,
+            order: self.best.order.clone(),
+            size: self.best.size,
+            found_by: self.best.found_by.to_string(),
+            evaluations: self.spent.1,
+            elapsed_secs: self.spent.0.as_secs_f64(),
+            finished,
+            // slices restart the strategies from the arm's best anyway
+            strategies: vec![],
+        }
+    }
+}
+
+// A guess at what a slice on the literal saves before it's been searched:
+// bigger literals have more bytes to win, and units whose simhashes are
+// spread out (some close pairs, some far apart) have more to gain from being
+// regrouped than ones that all look alike. Deliberately optimistic, real
+// slices pull it down quickly.
+fn expected_gain(problem: &Problem, size: usize) -> f64 {
+    let simhashes = problem::unit_simhashes(&problem.units);
+    let mut distances = vec![];
+    for (i, a) in simhashes.iter().enumerate() {
+        for b in &simhashes[i + 1..] {
+            distances.push(hamming_distance(*a, *b) as f64);
+        }
+    }
+    if distances.is_empty() {
+        return 0.0;
+    }
+    let mean = distances.iter().sum::<f64>() / distances.len() as f64;
+    let variance = distances.iter().map(|d| (d - mean) * (d - mean)).sum::<f64>() / distances.len() as f64;
+    size as f64 * variance.sqrt() / 64.0
+}
+
+/// Splits `budget` between the arms a slice at a time, UCB1 style: each
+/// slice goes to the literal with the best average savings per slice so far
+/// plus a bonus for having had fewer slices than the rest. Every slice races
+/// `strategies` from the literal's best order, like a regular search.
+/// `on_slice` gets called after each one, e.g. to checkpoint.
+pub fn run(
+    arms: &mut [Arm],
+    budget: Duration,
+    limits: &Limits,
+    strategies: &[Strategy],
+    threads: usize,
+    rng_seed: u64,
+    mut on_slice: impl FnMut(&Arm),
+) {
+    if arms.is_empty() {
+        return;
+    }
+    let start = Instant::now();
+    let slice_time = (budget / (SLICES_PER_LITERAL * arms.len() as u32)).max(MIN_SLICE);
+    // bonus on the scale of the gains themselves
+    let exploration = (arms.iter().map(|arm| arm.prior).sum::<f64>() / arms.len() as f64).max(1.0);
+    let mut total_slices = 0;
+
+    loop {
+        let remaining = budget.saturating_sub(start.elapsed());
+        if remaining.is_zero() || crate::interrupt::interrupted() {
+            break;
+        }
+        let score = |arm: &Arm| {
+            arm.mean_gain() + exploration * ((total_slices as f64 + 1.0).ln() / (arm.slices + 1) as f64).sqrt()
+        };
+        let Some(arm) = arms.iter_mut().filter(|arm| !arm.retired).max_by(|a, b| score(a).total_cmp(&score(b))) else {
+            break;
+        };
+
+        let slice_limits = Limits { time: slice_time.min(remaining), ..limits.remaining(arm.spent.0, arm.spent.1) };
+        let search = Search::new(&arm.problem, arm.best.clone(), &slice_limits);
+        let slice_seed = rng_seed ^ ((arm.span.0 as u64) << 32) ^ arm.slices as u64;
+        portfolio::run(&search, strategies, threads, slice_seed, |_| {});
+
+        let best = search.best();
+        println!("Slice {} on {}: {} -> {} bytes", arm.slices + 1, arm.label, arm.best.size, best.size);
+        arm.saved += arm.best.size - best.size;
+        arm.best = best;
+        arm.spent.0 += search.budget.elapsed();
+        arm.spent.1 += search.budget.evaluations();
+        arm.slices += 1;
+        total_slices += 1;
+        match search.budget.stop_reason() {
+            Some("time budget") | Some("interrupted") | None => {}
+            Some(_) => arm.retired = true,
+        }
+        on_slice(arm);
+    }
+
+    for arm in arms.iter() {
+        println!("{} got {} slices ({:.1}s), saving {} bytes", arm.label, arm.slices, arm.spent.0.as_secs_f64(), arm.saved);
+    }
+}
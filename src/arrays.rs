@@ -0,0 +1,180 @@
+use std::collections::HashSet;
+
+use swc_common::comments::SingleThreadedComments;
+use swc_common::sync::Lrc;
+use swc_common::SourceMap;
+use swc_ecma_ast::{ArrayLit, Expr, ExprOrSpread, KeyValueProp, Module, NewExpr, Pat, VarDeclarator};
+use swc_ecma_visit::{Visit, VisitWith};
+
+use crate::baselines::prop_name;
+use crate::pragmas::{self, Pragma};
+use crate::problem;
+use crate::purity;
+
+// element keys longer than this get hashed instead
+const MAX_KEY_LEN: usize = 32;
+
+/// Where the array literals whose order doesn't matter start. Arrays are
+/// left alone unless they're marked `/* @js_moving unordered */`, directly
+/// or through a marked `new Set([...])` around them, or bound to one of
+/// `names` (--unordered) as a variable or property, again directly or
+/// through `new Set`.
+pub fn unordered(module: &Module, comments: &SingleThreadedComments, names: &[String]) -> HashSet<u32> {
+    let mut marked = Marked { comments, names, starts: HashSet::new() };
+    module.visit_with(&mut marked);
+    marked.starts
+}
+
+struct Marked<'a> {
+    comments: &'a SingleThreadedComments,
+    names: &'a [String],
+    starts: HashSet<u32>,
+}
+
+impl<'a> Marked<'a> {
+    fn unordered(&self, lo: u32) -> bool {
+        pragmas::pragma(self.comments, lo) == Some(Pragma::Unordered)
+    }
+
+    fn mark_named(&mut self, name: Option<String>, value: Option<&Expr>) {
+        if let (Some(name), Some(value)) = (name, value) {
+            if self.names.contains(&name) {
+                self.starts.extend(array(value));
+            }
+        }
+    }
+}
+
+impl<'a> Visit for Marked<'a> {
+    fn visit_array_lit(&mut self, arr: &ArrayLit) {
+        if self.unordered(arr.span.lo.0) {
+            self.starts.insert(arr.span.lo.0);
+        }
+        arr.visit_children_with(self);
+    }
+
+    fn visit_new_expr(&mut self, new: &NewExpr) {
+        if self.unordered(new.span.lo.0) {
+            self.starts.extend(set_array(new));
+        }
+        new.visit_children_with(self);
+    }
+
+    fn visit_var_declarator(&mut self, decl: &VarDeclarator) {
+        let name = match &decl.name {
+            Pat::Ident(ident) => Some(ident.id.sym.to_string()),
+            _ => None,
+        };
+        self.mark_named(name, decl.init.as_deref());
+        decl.visit_children_with(self);
+    }
+
+    fn visit_key_value_prop(&mut self, kv: &KeyValueProp) {
+        self.mark_named(prop_name(&kv.key), Some(&kv.value));
+        kv.visit_children_with(self);
+    }
+}
+
+// The array literal `expr` is, or fills a `new Set` with.
+fn array(expr: &Expr) -> Option<u32> {
+    match expr {
+        Expr::Array(arr) => Some(arr.span.lo.0),
+        Expr::New(new) => set_array(new),
+        Expr::Paren(paren) => array(&paren.expr),
+        _ => None,
+    }
+}
+
+fn set_array(new: &NewExpr) -> Option<u32> {
+    if !matches!(&*new.callee, Expr::Ident(ident) if &*ident.sym == "Set") {
+        return None;
+    }
+    match new.args.as_deref()? {
+        [ExprOrSpread { spread: None, expr }] => match &**expr {
+            Expr::Array(arr) => Some(arr.span.lo.0),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// What has to stay ahead of what in an unordered array, the way
+/// `Problem::before` takes it: holes, spread elements and elements that run
+/// code when evaluated stay where they are, everything else can go anywhere
+/// between them. None if no two elements can trade places.
+pub fn constraints(arr: &ArrayLit) -> Option<Vec<Vec<usize>>> {
+    let free: Vec<bool> = arr
+        .elems
+        .iter()
+        .map(|elem| elem.as_ref().is_some_and(|elem| elem.spread.is_none() && purity::pure_expr(&elem.expr)))
+        .collect();
+    let before = problem::free_constraints(&free);
+    if problem::forced(&before) {
+        return None;
+    }
+    Some(before)
+}
+
+/// A label per element for manifests: its minified source, or a hash of it
+/// if that's long. Holes are "".
+pub fn keys(arr: &ArrayLit, cm: &Lrc<SourceMap>) -> Vec<String> {
+    arr.elems
+        .iter()
+        .map(|elem| {
+            let Some(elem) = elem else {
+                return String::new();
+            };
+            let source = crate::ast_bytes(elem, cm);
+            if source.len() <= MAX_KEY_LEN {
+                String::from_utf8_lossy(&source).into_owned()
+            } else {
+                format!("#{:016x}", crate::checkpoint::content_hash(&source))
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use swc_ecma_ast::{Decl, Expr, ModuleItem, Stmt};
+
+    // The array `const a = ...` starts with in `source`.
+    fn constraints(source: &str) -> Option<Vec<Vec<usize>>> {
+        let module = crate::parse_source(source);
+        let ModuleItem::Stmt(Stmt::Decl(Decl::Var(var))) = &module.body[0] else {
+            panic!("expected a declaration in {}", source);
+        };
+        let Some(Expr::Array(arr)) = var.decls[0].init.as_deref() else {
+            panic!("expected an array in {}", source);
+        };
+        super::constraints(arr)
+    }
+
+    #[test]
+    fn code_running_elements_stay_put() {
+        // 0 and 1 can swap, as can 3 and 4, but none of them crosses f()
+        let before = constraints(r#"const a = [1, "x", f(), 2, 3];"#).unwrap();
+        assert_eq!(before, vec![vec![], vec![], vec![0, 1], vec![2], vec![2]]);
+    }
+
+    #[test]
+    fn holes_and_spreads_stay_put() {
+        assert_eq!(constraints("const a = [1, , 2];"), None);
+        assert_eq!(constraints("const a = [...b, 1];"), None);
+        assert_eq!(constraints("const a = [1, 2, ...b];"), Some(vec![vec![], vec![], vec![0, 1]]));
+    }
+
+    #[test]
+    fn only_marked_or_named_arrays_are_unordered() {
+        let source = "const xs = [1, 2], ys = [3, 4]; const s = /* @js_moving unordered */ new Set([5, 6]); \
+                      const t = new Set([7, 8]); const o = {xs: [9, 0]}; const m = /* @js_moving unordered */ [1, 3];";
+        let (_, module, comments) = crate::parse_source_with_comments(source);
+        let at = |needle: &str| module.span.lo.0 + source.find(needle).unwrap() as u32;
+        let unordered = super::unordered(&module, &comments, &["xs".to_string()]);
+        let mut expected = vec![at("[1, 2]"), at("[5, 6]"), at("[9, 0]"), at("[1, 3]")];
+        let mut found: Vec<u32> = unordered.into_iter().collect();
+        expected.sort();
+        found.sort();
+        assert_eq!(found, expected);
+    }
+}
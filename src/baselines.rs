@@ -0,0 +1,215 @@
+use simhash::hamming_distance;
+use swc_common::sync::Lrc;
+use swc_common::SourceMap;
+use swc_ecma_ast::{Expr, Lit, Prop, PropName, PropOrSpread};
+
+use crate::problem::{self, Problem};
+
+/// Deterministic orders that cost nothing to compute next to a real search
+/// and often capture most of the win. Every entry is a permutation of
+/// 0..props.len(), and sorts are stable so ties keep source order.
+pub fn candidates(props: &[PropOrSpread], units: &[Vec<u8>], cm: &Lrc<SourceMap>) -> Vec<(&'static str, Vec<usize>)> {
+    let original: Vec<usize> = (0..props.len()).collect();
+
+    let keys: Vec<String> = props.iter().map(|prop| prop_key(prop).unwrap_or_default()).collect();
+    let values: Vec<Vec<u8>> = props
+        .iter()
+        .zip(units)
+        .map(|(prop, unit)| match prop_value(prop) {
+            Some(value) => crate::ast_bytes(value, cm),
+            None => unit.clone(),
+        })
+        .collect();
+    let kinds: Vec<&'static str> = props.iter().map(prop_kind).collect();
+
+    let mut candidates = vec![
+        ("original", original.clone()),
+        ("key", sorted_by(&original, |i| &keys[i])),
+        ("value", sorted_by(&original, |i| &values[i])),
+        ("value length", sorted_by(&original, |i| values[i].len())),
+        ("value kind", sorted_by(&original, |i| kinds[i])),
+    ];
+    candidates.extend(similarity_candidates(units));
+    candidates
+}
+
+/// The same for units labelled by name rather than key, like function runs
+/// or statements.
+pub fn labelled_candidates(labels: &[String], units: &[Vec<u8>]) -> Vec<(&'static str, Vec<usize>)> {
+    let original: Vec<usize> = (0..labels.len()).collect();
+    let mut candidates = vec![
+        ("original", original.clone()),
+        ("name", sorted_by(&original, |i| &labels[i])),
+        ("length", sorted_by(&original, |i| units[i].len())),
+    ];
+    candidates.extend(similarity_candidates(units));
+    candidates
+}
+
+// Orders that put units with similar content next to each other.
+fn similarity_candidates(units: &[Vec<u8>]) -> Vec<(&'static str, Vec<usize>)> {
+    let original: Vec<usize> = (0..units.len()).collect();
+    let simhashes = problem::unit_simhashes(units);
+    vec![
+        ("simhash gray code", sorted_by(&original, |i| gray_rank(simhashes[i]))),
+        ("clustering", cluster_order(&simhashes)),
+    ]
+}
+
+/// Evaluates every candidate and returns the smallest one's name, order and
+/// compressed size.
+pub fn best(problem: &Problem, candidates: Vec<(&'static str, Vec<usize>)>) -> (&'static str, Vec<usize>, usize) {
+    candidates
+        .into_iter()
+        .map(|(name, order)| {
+            let size = problem.size(&order);
+            println!("Baseline {:<18} {} bytes", name, size);
+            (name, order, size)
+        })
+        .min_by_key(|(_, _, size)| *size)
+        .unwrap()
+}
+
+fn sorted_by<K: Ord>(order: &[usize], key: impl Fn(usize) -> K) -> Vec<usize> {
+    let mut sorted = order.to_vec();
+    sorted.sort_by_key(|i| key(*i));
+    sorted
+}
+
+/// The prop's key, or None for spreads and computed keys.
+pub fn prop_key(prop: &PropOrSpread) -> Option<String> {
+    let name = match prop {
+        PropOrSpread::Spread(_) => return None,
+        PropOrSpread::Prop(prop) => match &**prop {
+            Prop::Shorthand(ident) => return Some(ident.sym.to_string()),
+            Prop::KeyValue(kv) => &kv.key,
+            Prop::Assign(assign) => return Some(assign.key.sym.to_string()),
+            Prop::Getter(getter) => &getter.key,
+            Prop::Setter(setter) => &setter.key,
+            Prop::Method(method) => &method.key,
+        },
+    };
+    prop_name(name)
+}
+
+/// A property name as a string, or None for computed ones.
+pub fn prop_name(name: &PropName) -> Option<String> {
+    match name {
+        PropName::Ident(ident) => Some(ident.sym.to_string()),
+        PropName::Str(str) => Some(str.value.to_string()),
+        PropName::Num(num) => Some(num.value.to_string()),
+        PropName::BigInt(big) => Some(big.value.to_string()),
+        PropName::Computed(_) => None,
+    }
+}
+
+fn prop_value(prop: &PropOrSpread) -> Option<&Expr> {
+    match prop {
+        PropOrSpread::Prop(prop) => match &**prop {
+            Prop::KeyValue(kv) => Some(&kv.value),
+            _ => None,
+        },
+        PropOrSpread::Spread(spread) => Some(&spread.expr),
+    }
+}
+
+fn prop_kind(prop: &PropOrSpread) -> &'static str {
+    let prop = match prop {
+        PropOrSpread::Spread(_) => return "spread",
+        PropOrSpread::Prop(prop) => prop,
+    };
+    let value = match &**prop {
+        Prop::KeyValue(kv) => &kv.value,
+        Prop::Shorthand(_) => return "shorthand",
+        Prop::Assign(_) => return "assign",
+        Prop::Getter(_) => return "getter",
+        Prop::Setter(_) => return "setter",
+        Prop::Method(_) => return "method",
+    };
+    match &**value {
+        Expr::Lit(Lit::Str(_)) => "string",
+        Expr::Lit(Lit::Num(_)) => "number",
+        Expr::Lit(Lit::Bool(_)) => "bool",
+        Expr::Lit(Lit::Null(_)) => "null",
+        Expr::Lit(_) => "literal",
+        Expr::Tpl(_) => "template",
+        Expr::Array(_) => "array",
+        Expr::Object(_) => "object",
+        Expr::Fn(_) => "function",
+        Expr::Arrow(_) => "arrow",
+        Expr::Class(_) => "class",
+        Expr::Call(_) | Expr::New(_) => "call",
+        Expr::Member(_) => "member",
+        Expr::Ident(_) => "ident",
+        _ => "other",
+    }
+}
+
+// Position of `hash` in the reflected binary Gray code sequence, so that
+// sorting by it puts hashes that differ in few high bits next to each other.
+fn gray_rank(hash: u64) -> u64 {
+    let mut rank = hash;
+    let mut shift = 1;
+    while shift < 64 {
+        rank ^= rank >> shift;
+        shift <<= 1;
+    }
+    rank
+}
+
+// Average-linkage agglomerative clustering over simhash hamming distance.
+// Each cluster keeps its leaves as a sequence, and merges join the two
+// sequences at whichever pair of ends is closest.
+fn cluster_order(simhashes: &[u64]) -> Vec<usize> {
+    let n = simhashes.len();
+    let mut clusters: Vec<Option<Vec<usize>>> = (0..n).map(|i| Some(vec![i])).collect();
+    let mut distances: Vec<Vec<f64>> = simhashes
+        .iter()
+        .map(|a| simhashes.iter().map(|b| hamming_distance(*a, *b) as f64).collect())
+        .collect();
+    let leaf_distance = |a: usize, b: usize| hamming_distance(simhashes[a], simhashes[b]);
+
+    for _ in 1..n {
+        let mut closest = (f64::MAX, 0, 0);
+        for a in 0..n {
+            if clusters[a].is_none() {
+                continue;
+            }
+            for b in a + 1..n {
+                if clusters[b].is_some() && distances[a][b] < closest.0 {
+                    closest = (distances[a][b], a, b);
+                }
+            }
+        }
+        let (_, a, b) = closest;
+
+        let mut left = clusters[a].take().unwrap();
+        let mut right = clusters[b].take().unwrap();
+        let (left_len, right_len) = (left.len() as f64, right.len() as f64);
+        let joins = [
+            leaf_distance(left[left.len() - 1], right[0]),
+            leaf_distance(left[left.len() - 1], right[right.len() - 1]),
+            leaf_distance(left[0], right[0]),
+            leaf_distance(left[0], right[right.len() - 1]),
+        ];
+        let best_join = (0..4).min_by_key(|join| joins[*join]).unwrap();
+        if best_join >= 2 {
+            left.reverse();
+        }
+        if best_join % 2 == 1 {
+            right.reverse();
+        }
+        left.extend(right);
+        clusters[a] = Some(left);
+
+        for other in 0..n {
+            if other != a && clusters[other].is_some() {
+                let merged = (distances[a][other] * left_len + distances[b][other] * right_len) / (left_len + right_len);
+                distances[a][other] = merged;
+                distances[other][a] = merged;
+            }
+        }
+    }
+
+    clusters.into_iter().flatten().next().unwrap_or_default()
+}
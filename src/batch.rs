@@ -0,0 +1,160 @@
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+use crate::options::Options;
+
+// suffix for outputs written next to their inputs; files with it are never
+// picked up as inputs so reruns don't optimize their own output
+const OUTPUT_SUFFIX: &str = ".out.js";
+
+/// Sizes before and after for one optimized file, or why it couldn't be.
+pub struct FileReport {
+    pub path: PathBuf,
+    pub sizes: Result<(usize, usize), String>,
+}
+
+/// Optimizes every matching .js file under the batch directory, `jobs` files
+/// at a time. The overall time budget is split between files by size, so
+/// the whole batch takes roughly that long. A file that fails, e.g. because
+/// it doesn't parse, gets reported as such without stopping the rest.
+pub fn run(options: &Options) {
+    let root = options.batch_dir.as_ref().unwrap();
+    let include = glob_set(&options.include);
+    let exclude = glob_set(&options.exclude);
+
+    // canonical, so it's recognized however either side spells it; one that
+    // doesn't exist yet can't be inside the input tree
+    let out_dir = options.out_dir.as_deref().and_then(|out_dir| out_dir.canonicalize().ok());
+    let mut files = vec![];
+    walk(root, root, out_dir.as_deref(), &include, &exclude, &mut files);
+    if files.is_empty() {
+        println!("No .js files to optimize under {}", root.display());
+        return;
+    }
+    let sizes: Vec<u64> = files.iter().map(|file| std::fs::metadata(root.join(file)).unwrap().len()).collect();
+    let total_size: u64 = sizes.iter().sum();
+
+    let file_count = files.len();
+    let jobs = options.jobs.clamp(1, file_count);
+    let threads_per_file = (options.threads / jobs).max(1);
+    let total_budget = options.limits.time * jobs as u32;
+
+    // largest first, so one big file doesn't start last and hold everyone up
+    let mut queue: Vec<(PathBuf, u64)> = files.into_iter().zip(sizes).collect();
+    queue.sort_by_key(|(_, size)| *size);
+    let queue = Mutex::new(queue);
+    let reports = Mutex::new(vec![]);
+
+    std::thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| loop {
+                // popped on its own line so the lock isn't held while optimizing
+                let next = queue.lock().unwrap().pop();
+                let Some((file, size)) = next else {
+                    break;
+                };
+                let mut file_options = options.clone();
+                file_options.threads = threads_per_file;
+                // evenly if every file is empty
+                let share = if total_size == 0 { 1.0 / file_count as f64 } else { size as f64 / total_size as f64 };
+                file_options.file_budget = Some(total_budget.mul_f64(share));
+
+                let input = root.join(&file);
+                let output = output_path(options, &file);
+                if let Some(parent) = output.parent() {
+                    std::fs::create_dir_all(parent).expect("failed to create output directory");
+                }
+                let sizes = panic::catch_unwind(AssertUnwindSafe(|| crate::optimize_file(&file_options, &input, &output)))
+                    .map_err(panic_message);
+                if let Err(message) = &sizes {
+                    println!("Failed to optimize {}: {}", input.display(), message);
+                }
+                reports.lock().unwrap().push(FileReport { path: file, sizes });
+            });
+        }
+    });
+
+    let mut reports = reports.into_inner().unwrap();
+    reports.sort_by(|a, b| a.path.cmp(&b.path));
+    print_table(&reports);
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload.downcast_ref::<&str>().map_or_else(|| "unknown error".to_string(), |message| message.to_string()),
+    }
+}
+
+pub fn glob_set(patterns: &[String]) -> GlobSet {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).unwrap_or_else(|e| panic!("bad glob {}: {}", pattern, e)));
+    }
+    builder.build().unwrap()
+}
+
+// Collects .js files under `dir` as paths relative to `root`, staying out of
+// the output directory (canonicalized) in case it's inside the input tree.
+fn walk(root: &Path, dir: &Path, out_dir: Option<&Path>, include: &GlobSet, exclude: &GlobSet, files: &mut Vec<PathBuf>) {
+    let mut entries: Vec<_> = std::fs::read_dir(dir)
+        .unwrap_or_else(|e| panic!("failed to read {}: {}", dir.display(), e))
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            if out_dir.is_none() || path.canonicalize().ok().as_deref() != out_dir {
+                walk(root, &path, out_dir, include, exclude, files);
+            }
+            continue;
+        }
+        let relative = path.strip_prefix(root).unwrap().to_path_buf();
+        let name = relative.to_string_lossy();
+        if !name.ends_with(".js") || name.ends_with(OUTPUT_SUFFIX) {
+            continue;
+        }
+        if (include.is_empty() || include.is_match(&relative)) && !exclude.is_match(&relative) {
+            files.push(relative);
+        }
+    }
+}
+
+// Mirrors the input tree under --out-dir, or writes next to the input.
+fn output_path(options: &Options, file: &Path) -> PathBuf {
+    match &options.out_dir {
+        Some(out_dir) => out_dir.join(file),
+        None => {
+            let input = options.batch_dir.as_ref().unwrap().join(file).to_string_lossy().into_owned();
+            PathBuf::from(format!("{}{}", input.strip_suffix(".js").unwrap(), OUTPUT_SUFFIX))
+        }
+    }
+}
+
+fn print_table(reports: &[FileReport]) {
+    let width = reports.iter().map(|report| report.path.to_string_lossy().len()).max().unwrap_or(0).max(5);
+    println!("{:<width$}  {:>10}  {:>10}  {:>8}  {:>7}", "file", "before", "after", "saved", "%", width = width);
+    let mut total = (0, 0);
+    for report in reports {
+        let name = report.path.to_string_lossy();
+        match &report.sizes {
+            Ok((initial_size, final_size)) => {
+                print_row(&name, *initial_size, *final_size, width);
+                total.0 += initial_size;
+                total.1 += final_size;
+            }
+            Err(message) => println!("{:<width$}  failed: {}", name, message, width = width),
+        }
+    }
+    print_row("total", total.0, total.1, width);
+}
+
+fn print_row(name: &str, initial_size: usize, final_size: usize, width: usize) {
+    let saved = initial_size as isize - final_size as isize;
+    let percent = if initial_size == 0 { 0.0 } else { saved as f64 * 100.0 / initial_size as f64 };
+    println!("{:<width$}  {:>10}  {:>10}  {:>8}  {:>6.2}%", name, initial_size, final_size, saved, percent, width = width);
+}
@@ -0,0 +1,192 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// how often the bytes-per-minute rate is re-measured
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Limits on how long a search may run. Any limit that's hit stops it.
+#[derive(Clone, Debug)]
+pub struct Limits {
+    pub time: Duration,
+    pub evaluations: Option<u64>,
+    // converged once this many evaluations in a row found nothing smaller
+    pub patience: Option<u64>,
+    // converged once a minute goes by saving fewer than this many bytes
+    pub min_bytes_per_minute: Option<f64>,
+}
+
+impl Limits {
+    /// What's left of these limits for a search that already ran for
+    /// `elapsed` and used `evaluations`, e.g. before a checkpoint.
+    pub fn remaining(&self, elapsed: Duration, evaluations: u64) -> Limits {
+        Limits {
+            time: self.time.saturating_sub(elapsed),
+            evaluations: self.evaluations.map(|max| max.saturating_sub(evaluations)),
+            ..self.clone()
+        }
+    }
+}
+
+/// Shared by every worker on one problem: counts evaluations, tracks when
+/// the best size last moved and decides when it's time to stop.
+pub struct Budget {
+    limits: Limits,
+    start: Instant,
+    deadline: Instant,
+    evaluations: AtomicU64,
+    best_size: AtomicUsize,
+    last_improvement: AtomicU64,
+    // start of the current rate window and the best size at that point
+    window: Mutex<(Instant, usize)>,
+    stop_reason: Mutex<Option<&'static str>>,
+}
+
+impl Budget {
+    pub fn new(limits: &Limits, initial_size: usize) -> Budget {
+        let now = Instant::now();
+        Budget {
+            limits: limits.clone(),
+            start: now,
+            deadline: now + limits.time,
+            evaluations: AtomicU64::new(0),
+            best_size: AtomicUsize::new(initial_size),
+            last_improvement: AtomicU64::new(0),
+            window: Mutex::new((now, initial_size)),
+            stop_reason: Mutex::new(None),
+        }
+    }
+
+    /// Counts one evaluation that came out at `size`.
+    pub fn record(&self, size: usize) {
+        let evaluation = self.evaluations.fetch_add(1, Ordering::AcqRel) + 1;
+        if size < self.best_size.fetch_min(size, Ordering::AcqRel) {
+            self.last_improvement.store(evaluation, Ordering::Release);
+        }
+    }
+
+    pub fn evaluations(&self) -> u64 {
+        self.evaluations.load(Ordering::Acquire)
+    }
+
+    /// Whether the search should stop. Once this returns true it keeps
+    /// returning true, and `stop_reason` says why.
+    pub fn exhausted(&self) -> bool {
+        let mut stop_reason = self.stop_reason.lock().unwrap();
+        if stop_reason.is_none() {
+            *stop_reason = self.check();
+        }
+        stop_reason.is_some()
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    pub fn remaining_time(&self) -> Duration {
+        self.deadline.saturating_duration_since(Instant::now())
+    }
+
+    /// How far along the tightest of the time and evaluation budgets is,
+    /// from 0 to 1.
+    pub fn portion_used(&self) -> f64 {
+        let time = 1.0 - self.remaining_time().as_secs_f64() / self.limits.time.as_secs_f64();
+        let evaluations = match self.limits.evaluations {
+            Some(max) => self.evaluations() as f64 / max as f64,
+            None => 0.0,
+        };
+        time.max(evaluations).min(1.0)
+    }
+
+    pub fn stop_reason(&self) -> Option<&'static str> {
+        *self.stop_reason.lock().unwrap()
+    }
+
+    fn check(&self) -> Option<&'static str> {
+        if crate::interrupt::interrupted() {
+            return Some("interrupted");
+        }
+        let now = Instant::now();
+        if now >= self.deadline {
+            return Some("time budget");
+        }
+        let evaluations = self.evaluations();
+        if self.limits.evaluations.is_some_and(|max| evaluations >= max) {
+            return Some("evaluation budget");
+        }
+        // another thread can record an improvement after we read the count
+        let idle = evaluations.saturating_sub(self.last_improvement.load(Ordering::Acquire));
+        if self.limits.patience.is_some_and(|patience| idle >= patience) {
+            return Some("no improvement");
+        }
+        if let Some(min_rate) = self.limits.min_bytes_per_minute {
+            let mut window = self.window.lock().unwrap();
+            let elapsed = now.duration_since(window.0);
+            if elapsed >= RATE_WINDOW {
+                let best_size = self.best_size.load(Ordering::Acquire);
+                let rate = (window.1 - best_size) as f64 / elapsed.as_secs_f64() * 60.0;
+                if rate < min_rate {
+                    return Some("savings rate");
+                }
+                *window = (now, best_size);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Budget, Limits};
+
+    fn limits() -> Limits {
+        Limits { time: Duration::from_secs(60), evaluations: Some(10), patience: Some(3), min_bytes_per_minute: None }
+    }
+
+    #[test]
+    fn remaining_takes_off_what_was_spent() {
+        let remaining = limits().remaining(Duration::from_secs(45), 4);
+        assert_eq!(remaining.time, Duration::from_secs(15));
+        assert_eq!(remaining.evaluations, Some(6));
+        let overspent = limits().remaining(Duration::from_secs(90), 40);
+        assert_eq!((overspent.time, overspent.evaluations), (Duration::ZERO, Some(0)));
+        assert_eq!(overspent.patience, Some(3));
+    }
+
+    #[test]
+    fn stops_on_evaluations() {
+        let budget = Budget::new(&Limits { patience: None, ..limits() }, 100);
+        for size in (90..100).rev() {
+            assert!(!budget.exhausted());
+            budget.record(size);
+        }
+        assert!(budget.exhausted());
+        assert_eq!(budget.stop_reason(), Some("evaluation budget"));
+        assert_eq!(budget.portion_used(), 1.0);
+    }
+
+    #[test]
+    fn stops_on_patience_counting_from_the_last_improvement() {
+        let budget = Budget::new(&limits(), 100);
+        budget.record(99);
+        budget.record(100);
+        budget.record(100);
+        assert!(!budget.exhausted());
+        budget.record(98);
+        budget.record(98);
+        budget.record(99);
+        assert!(!budget.exhausted());
+        budget.record(99);
+        assert!(budget.exhausted());
+        assert_eq!(budget.stop_reason(), Some("no improvement"));
+    }
+
+    #[test]
+    fn stops_on_time() {
+        let budget = Budget::new(&Limits { time: Duration::ZERO, ..limits() }, 100);
+        assert!(budget.exhausted());
+        assert_eq!(budget.stop_reason(), Some("time budget"));
+    }
+}
@@ -0,0 +1,105 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::checkpoint::content_hash;
+use crate::problem::Problem;
+
+/// Orders found by earlier runs, one small JSON file per literal, keyed by
+/// the literal's serialized props, what has to stay ahead of what and
+/// everything else that decides what the best order is.
+pub struct Cache {
+    dir: PathBuf,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Cached {
+    order: Vec<usize>,
+    size: usize,
+}
+
+impl Cache {
+    pub fn new(dir: PathBuf) -> Cache {
+        std::fs::create_dir_all(&dir).expect("failed to create cache directory");
+        Cache { dir }
+    }
+
+    /// `method` is how the order gets picked, e.g. "exact" or the list of
+    /// strategies raced.
+    pub fn key(problem: &Problem, method: &str) -> u64 {
+        let mut bytes = vec![];
+        for unit in &problem.units {
+            bytes.extend_from_slice(&(unit.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(unit);
+        }
+        // the same props can be pinned differently, e.g. with other
+        // --side-effect-free modules
+        for before in &problem.before {
+            bytes.extend_from_slice(&(before.len() as u64).to_le_bytes());
+            for unit in before {
+                bytes.extend_from_slice(&(*unit as u64).to_le_bytes());
+            }
+        }
+        bytes.extend_from_slice(format!("brotli {} {}", problem.quality, method).as_bytes());
+        content_hash(&bytes)
+    }
+
+    pub fn get(&self, key: u64, num_units: usize) -> Option<(Vec<usize>, usize)> {
+        let json = std::fs::read_to_string(self.path(key)).ok()?;
+        let cached: Cached = serde_json::from_str(&json).ok()?;
+        // a hash collision or hand-edited file shouldn't turn into a panic
+        let mut sorted = cached.order.clone();
+        sorted.sort_unstable();
+        if !sorted.iter().copied().eq(0..num_units) {
+            return None;
+        }
+        Some((cached.order, cached.size))
+    }
+
+    pub fn put(&self, key: u64, order: &[usize], size: usize) {
+        let cached = Cached { order: order.to_vec(), size };
+        std::fs::write(self.path(key), serde_json::to_string(&cached).unwrap()).expect("failed to write cache entry");
+    }
+
+    fn path(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{:016x}.json", key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Cache;
+    use crate::problem::Problem;
+
+    fn problem(before: Vec<Vec<usize>>) -> Problem {
+        let units = vec![b"a:1".to_vec(), b"b:2".to_vec(), b"c:3".to_vec()];
+        Problem { prefix: vec![], units, separator: b",".to_vec(), suffix: vec![], quality: 11, before }
+    }
+
+    #[test]
+    fn keys_tell_apart_everything_that_decides_the_order() {
+        let key = Cache::key(&problem(vec![]), "exact");
+        assert_eq!(key, Cache::key(&problem(vec![]), "exact"));
+        assert_ne!(key, Cache::key(&problem(vec![]), "hill,anneal"));
+        assert_ne!(key, Cache::key(&problem(vec![vec![], vec![0], vec![]]), "exact"));
+        assert_ne!(Cache::key(&problem(vec![vec![], vec![0], vec![]]), "exact"), Cache::key(&problem(vec![vec![], vec![], vec![0]]), "exact"));
+        assert_ne!(key, Cache::key(&Problem { quality: 9, ..problem(vec![]) }, "exact"));
+        let mut other = problem(vec![]);
+        other.units.swap(0, 1);
+        assert_ne!(key, Cache::key(&other, "exact"));
+    }
+
+    #[test]
+    fn entries_come_back_only_as_permutations() {
+        let dir = std::env::temp_dir().join(format!("js_moving_cache_test_{}", std::process::id()));
+        let cache = Cache::new(dir.clone());
+        cache.put(1, &[2, 0, 1], 10);
+        assert_eq!(cache.get(1, 3), Some((vec![2, 0, 1], 10)));
+        // the same key for a different number of units is a collision
+        assert_eq!(cache.get(1, 4), None);
+        cache.put(2, &[0, 0, 1], 10);
+        assert_eq!(cache.get(2, 3), None);
+        assert_eq!(cache.get(3, 3), None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
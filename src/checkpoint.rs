@@ -0,0 +1,117 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::strategies::{State, Strategy};
+
+/// Everything needed to pick a long run back up: the best order found for
+/// each literal so far and how much of its budget that took. Literals are
+/// identified by kind and span (a run of functions can span exactly what its
+/// statement list does), which is only meaningful against the exact same
+/// input, hence the content hash. Strategies' own state (populations,
+/// annealing's order and temperature, where climbs got to) is saved along
+/// with it, so a resumed search carries on rather than starting over.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub input_hash: u64,
+    pub seed: u64,
+    pub literals: Vec<LiteralState>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LiteralState {
+    // the target's kind, e.g. "props" or "statements"
+    #[serde(default)]
+    pub kind: String,
+    pub span: (u32, u32),
+    // indices into the literal's props as they appear in the input
+    pub order: Vec<usize>,
+    pub size: usize,
+    pub found_by: String,
+    pub evaluations: u64,
+    pub elapsed_secs: f64,
+    // false while the literal is still being searched
+    pub finished: bool,
+    // where each strategy got to, for a search that isn't finished
+    #[serde(default)]
+    pub strategies: Vec<(Strategy, State)>,
+}
+
+impl LiteralState {
+    pub fn is(&self, kind: &str, span: (u32, u32)) -> bool {
+        self.kind == kind && self.span == span
+    }
+}
+
+impl Checkpoint {
+    pub fn new(input_hash: u64, seed: u64) -> Checkpoint {
+        Checkpoint { input_hash, seed, literals: vec![] }
+    }
+
+    /// Loads a checkpoint, refusing one that was written for different input.
+    pub fn load(path: &Path, input_hash: u64) -> Checkpoint {
+        let json = std::fs::read_to_string(path).expect("failed to read checkpoint");
+        let checkpoint: Checkpoint = serde_json::from_str(&json).expect("failed to parse checkpoint");
+        if checkpoint.input_hash != input_hash {
+            panic!("checkpoint {} was written for different input, refusing to resume", path.display());
+        }
+        checkpoint
+    }
+
+    /// Writes to a temporary file first so a kill mid-write can't leave a
+    /// truncated checkpoint behind.
+    pub fn save(&self, path: &Path) {
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_string(self).unwrap()).expect("failed to write checkpoint");
+        std::fs::rename(&tmp, path).expect("failed to write checkpoint");
+    }
+
+    pub fn get(&self, kind: &str, span: (u32, u32)) -> Option<&LiteralState> {
+        self.literals.iter().find(|literal| literal.is(kind, span))
+    }
+
+    pub fn update(&mut self, state: LiteralState) {
+        match self.literals.iter_mut().find(|literal| literal.is(&state.kind, state.span)) {
+            Some(literal) => *literal = state,
+            None => self.literals.push(state),
+        }
+    }
+}
+
+/// 64-bit FNV-1a. Stable across builds and platforms, unlike `DefaultHasher`.
+pub fn content_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Checkpoint, LiteralState};
+
+    fn state(kind: &str, size: usize, finished: bool) -> LiteralState {
+        LiteralState {
+            kind: kind.to_string(),
+            span: (0, 144),
+            order: vec![1, 0],
+            size,
+            found_by: "exact".to_string(),
+            evaluations: 0,
+            elapsed_secs: 0.0,
+            finished,
+            strategies: vec![],
+        }
+    }
+
+    #[test]
+    fn kinds_sharing_a_span_are_kept_apart() {
+        // a statement list of nothing but functions spans what their run does
+        let mut checkpoint = Checkpoint::new(0, 0);
+        checkpoint.update(state("functions", 54, true));
+        checkpoint.update(state("statements", 57, false));
+        assert_eq!(checkpoint.get("functions", (0, 144)).map(|state| (state.size, state.finished)), Some((54, true)));
+        assert_eq!(checkpoint.get("statements", (0, 144)).map(|state| (state.size, state.finished)), Some((57, false)));
+        checkpoint.update(state("statements", 56, true));
+        assert_eq!(checkpoint.literals.len(), 2);
+        assert_eq!(checkpoint.get("statements", (0, 144)).map(|state| state.size), Some(56));
+        assert!(checkpoint.get("props", (0, 144)).is_none());
+    }
+}
@@ -0,0 +1,66 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use swc_common::comments::{Comment, SingleThreadedComments, SingleThreadedCommentsMapInner};
+
+use crate::options::CommentPolicy;
+
+/// A copy of `comments` with only the ones `policy` keeps. The emitter takes
+/// comments out as it writes them, so every emit needs its own copy.
+pub fn kept(comments: &SingleThreadedComments, policy: CommentPolicy) -> SingleThreadedComments {
+    let (leading, trailing) = comments.borrow_all();
+    let filter = |map: &SingleThreadedCommentsMapInner| {
+        let filtered: SingleThreadedCommentsMapInner = map
+            .iter()
+            .map(|(pos, comments)| (*pos, comments.iter().filter(|comment| keep(comment, policy)).cloned().collect::<Vec<_>>()))
+            .filter(|(_, comments)| !comments.is_empty())
+            .collect();
+        Rc::new(RefCell::new(filtered))
+    };
+    SingleThreadedComments::from_leading_and_trailing(filter(&leading), filter(&trailing))
+}
+
+fn keep(comment: &Comment, policy: CommentPolicy) -> bool {
+    match policy {
+        CommentPolicy::None => false,
+        CommentPolicy::Legal => legal(&comment.text),
+        CommentPolicy::All => true,
+    }
+}
+
+// The usual convention for comments minifiers have to keep: `/*!` or `//!`,
+// or an @license or @preserve tag anywhere in them.
+fn legal(text: &str) -> bool {
+    text.starts_with('!') || text.contains("@license") || text.contains("@preserve")
+}
+
+#[cfg(test)]
+mod tests {
+    use swc_common::comments::Comments;
+    use swc_common::Spanned;
+
+    use crate::options::CommentPolicy;
+
+    // The text of the comments `policy` keeps in front of the first statement.
+    fn kept(policy: CommentPolicy) -> Vec<String> {
+        let source = "/*! header */ /* @js_moving keep-order */ // @license MIT\n/* note */ const a = 1;";
+        let (_, module, comments) = crate::parse_source_with_comments(source);
+        let kept = super::kept(&comments, policy);
+        let lo = module.body[0].span().lo;
+        kept.get_leading(lo).unwrap_or_default().into_iter().map(|comment| comment.text.to_string()).collect()
+    }
+
+    #[test]
+    fn policies_keep_what_they_say() {
+        assert!(kept(CommentPolicy::None).is_empty());
+        assert_eq!(kept(CommentPolicy::Legal), vec!["! header ", " @license MIT"]);
+        assert_eq!(kept(CommentPolicy::All).len(), 4);
+    }
+
+    #[test]
+    fn the_original_comments_are_left_alone() {
+        let (_, module, comments) = crate::parse_source_with_comments("/* a */ /*! b */ f();");
+        super::kept(&comments, CommentPolicy::None);
+        assert_eq!(comments.get_leading(module.body[0].span().lo).map(|comments| comments.len()), Some(2));
+    }
+}
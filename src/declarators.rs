@@ -0,0 +1,77 @@
+use std::collections::HashSet;
+
+use swc_ecma_ast::{Pat, VarDecl};
+
+use crate::purity;
+use crate::statements;
+
+/// Whether a declaration's declarators can go in any order: each binds a
+/// plain name no other one binds, and its initializer is pure and mentions
+/// none of the names declared alongside it, so no declarator can tell
+/// whether the others have run yet.
+pub fn reorderable(var: &VarDecl) -> bool {
+    if var.decls.len() < 2 {
+        return false;
+    }
+    let mut names = HashSet::new();
+    for decl in &var.decls {
+        match &decl.name {
+            Pat::Ident(ident) if names.insert(ident.id.sym.to_string()) => {}
+            _ => return false,
+        }
+    }
+    var.decls.iter().all(|decl| {
+        decl.init.as_deref().is_none_or(|init| {
+            purity::pure_expr(init) && statements::mentioned(init).iter().all(|name| !names.contains(name))
+        })
+    })
+}
+
+/// A label per declarator for manifests: the name it declares.
+pub fn keys(var: &VarDecl) -> Vec<String> {
+    var.decls
+        .iter()
+        .map(|decl| match &decl.name {
+            Pat::Ident(ident) => ident.id.sym.to_string(),
+            _ => unreachable!("only plain names get reordered"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use swc_ecma_ast::{Decl, ModuleItem, Stmt, VarDecl};
+
+    fn declaration(source: &str) -> VarDecl {
+        match crate::parse_source(source).body.remove(0) {
+            ModuleItem::Stmt(Stmt::Decl(Decl::Var(var))) => var,
+            _ => panic!("expected a declaration in {}", source),
+        }
+    }
+
+    fn reorderable(source: &str) -> bool {
+        super::reorderable(&declaration(source))
+    }
+
+    #[test]
+    fn independent_pure_declarators_are_reorderable() {
+        assert!(reorderable(r#"var a = 1, b = "x", c = [1, 2];"#));
+        assert!(reorderable("let a, b;"));
+        assert_eq!(super::keys(&declaration("const b = 1, a = 2;")), vec!["b", "a"]);
+    }
+
+    #[test]
+    fn declarators_that_could_tell_the_difference_stay_put() {
+        // only one
+        assert!(!reorderable("var a = 1;"));
+        // b reads a
+        assert!(!reorderable("var a = 1, b = a;"));
+        assert!(!reorderable("var a = 1, b = [a];"));
+        // f could read either
+        assert!(!reorderable("var a = f(), b = 1;"));
+        // the later one wins
+        assert!(!reorderable("var a = 1, a = 2;"));
+        // destructuring reads the source
+        assert!(!reorderable("var {a} = o, b = 1;"));
+    }
+}
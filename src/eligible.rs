@@ -0,0 +1,233 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+use swc_common::comments::SingleThreadedComments;
+use swc_common::Spanned;
+use swc_ecma_ast::{ArrayLit, Class, ImportDecl, Module, ModuleItem, NamedExport, ObjectLit, ObjectPat, Stmt, SwitchStmt, VarDecl};
+use swc_ecma_visit::{Visit, VisitWith};
+
+use crate::arrays;
+use crate::functions;
+use crate::imports;
+use crate::pragmas::{self, Pragma};
+
+/// Which object literals and arrays get reordered, worked out once up front
+/// so everything walking the module agrees. Literals are, down to
+/// --max-depth, and deeper ones marked `/* @js_moving unordered */`; arrays
+/// only if `arrays::unordered` finds them. Neither is if marked keep-order.
+/// Also which targets keep-order pragmas are about.
+pub struct Eligible {
+    literals: HashSet<u32>,
+    arrays: HashSet<u32>,
+    // where keep-order pragmas are, and how far out the innermost target
+    // starting there reaches
+    kept: HashMap<u32, Reach>,
+}
+
+/// How far out a target reaches from where it starts. A pragma in front of
+/// several targets at once is about the innermost: one before a leading
+/// `var` keeps its declarators in order, not the statements it starts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Reach {
+    // a node's own parts, like a literal's props or a class's members
+    Node,
+    // a run of functions or imports
+    Run,
+    // a whole statement list
+    List,
+}
+
+impl Eligible {
+    pub fn literal(&self, lit: &ObjectLit) -> bool {
+        self.literal_at(lit.span.lo.0)
+    }
+
+    /// Whether the literal starting at `lo` is.
+    pub fn literal_at(&self, lo: u32) -> bool {
+        self.literals.contains(&lo)
+    }
+
+    pub fn array(&self, arr: &ArrayLit) -> bool {
+        self.arrays.contains(&arr.span.lo.0)
+    }
+
+    /// Whether the target reaching `reach` from `lo` is marked keep-order.
+    pub fn keep_order(&self, reach: Reach, lo: u32) -> bool {
+        self.kept.get(&lo) == Some(&reach)
+    }
+}
+
+/// `unordered` is --unordered.
+pub fn collect(module: &Module, comments: &SingleThreadedComments, max_depth: usize, unordered: &[String]) -> Eligible {
+    let mut literals = Literals { comments, max_depth, depth: 0, starts: HashSet::new(), kept: HashMap::new() };
+    module.visit_with(&mut literals);
+    let mut arrays = arrays::unordered(module, comments, unordered);
+    arrays.retain(|lo| pragmas::pragma(comments, *lo) != Some(Pragma::KeepOrder));
+    Eligible { literals: literals.starts, arrays, kept: literals.kept }
+}
+
+struct Literals<'a> {
+    comments: &'a SingleThreadedComments,
+    max_depth: usize,
+    // how many literals deep we are, 1 inside an outermost one
+    depth: usize,
+    starts: HashSet<u32>,
+    kept: HashMap<u32, Reach>,
+}
+
+impl<'a> Literals<'a> {
+    fn note(&mut self, lo: u32, reach: Reach) {
+        if pragmas::pragma(self.comments, lo) == Some(Pragma::KeepOrder) {
+            let innermost = self.kept.entry(lo).or_insert(reach);
+            *innermost = (*innermost).min(reach);
+        }
+    }
+
+    fn note_list<T: Spanned>(&mut self, items: &[T], runs: Vec<Range<usize>>) {
+        if let Some(first) = items.first() {
+            self.note(first.span().lo.0, Reach::List);
+        }
+        for run in runs {
+            self.note(items[run.start].span().lo.0, Reach::Run);
+        }
+    }
+}
+
+impl<'a> Visit for Literals<'a> {
+    fn visit_module_items(&mut self, items: &[ModuleItem]) {
+        items.visit_children_with(self);
+        let mut runs = functions::runs(items, functions::item_decl);
+        runs.extend(imports::runs(items));
+        self.note_list(items, runs);
+    }
+
+    fn visit_stmts(&mut self, stmts: &[Stmt]) {
+        stmts.visit_children_with(self);
+        self.note_list(stmts, functions::runs(stmts, functions::stmt_decl));
+    }
+
+    fn visit_object_lit(&mut self, lit: &ObjectLit) {
+        self.note(lit.span.lo.0, Reach::Node);
+        self.depth += 1;
+        let eligible = match pragmas::pragma(self.comments, lit.span.lo.0) {
+            Some(Pragma::KeepOrder) => false,
+            Some(Pragma::Unordered) => true,
+            None => self.depth <= self.max_depth,
+        };
+        if eligible {
+            self.starts.insert(lit.span.lo.0);
+        }
+        lit.visit_children_with(self);
+        self.depth -= 1;
+    }
+
+    fn visit_array_lit(&mut self, arr: &ArrayLit) {
+        self.note(arr.span.lo.0, Reach::Node);
+        arr.visit_children_with(self);
+    }
+
+    fn visit_class(&mut self, class: &Class) {
+        self.note(class.span.lo.0, Reach::Node);
+        class.visit_children_with(self);
+    }
+
+    fn visit_var_decl(&mut self, var: &VarDecl) {
+        self.note(var.span.lo.0, Reach::Node);
+        var.visit_children_with(self);
+    }
+
+    fn visit_switch_stmt(&mut self, switch: &SwitchStmt) {
+        self.note(switch.span.lo.0, Reach::Node);
+        switch.visit_children_with(self);
+    }
+
+    fn visit_object_pat(&mut self, pat: &ObjectPat) {
+        self.note(pat.span.lo.0, Reach::Node);
+        pat.visit_children_with(self);
+    }
+
+    fn visit_import_decl(&mut self, decl: &ImportDecl) {
+        self.note(decl.span.lo.0, Reach::Node);
+        decl.visit_children_with(self);
+    }
+
+    fn visit_named_export(&mut self, export: &NamedExport) {
+        self.note(export.span.lo.0, Reach::Node);
+        export.visit_children_with(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use swc_common::Spanned;
+    use swc_ecma_ast::{Decl, Expr, ModuleItem, Stmt};
+
+    use super::{Eligible, Reach};
+
+    // Eligibility for `source`, and where each of its module items starts.
+    fn collect(source: &str, max_depth: usize) -> (Eligible, Vec<u32>) {
+        let (_, module, comments) = crate::parse_source_with_comments(source);
+        let starts = module.body.iter().map(|item| item.span().lo.0).collect();
+        (super::collect(&module, &comments, max_depth, &[]), starts)
+    }
+
+    #[test]
+    fn keep_order_goes_to_the_innermost_target() {
+        // the var's declarators, not the statements it starts
+        let (eligible, starts) = collect("/* @js_moving keep-order */ var a = 1, b = 2; f(); g();", usize::MAX);
+        assert!(eligible.keep_order(Reach::Node, starts[0]));
+        assert!(!eligible.keep_order(Reach::List, starts[0]));
+
+        // the function run, not the statements
+        let (eligible, starts) = collect("/* @js_moving keep-order */ function a() {} function b() {} f();", usize::MAX);
+        assert!(eligible.keep_order(Reach::Run, starts[0]));
+        assert!(!eligible.keep_order(Reach::List, starts[0]));
+
+        // nothing smaller starts here, so the statements
+        let (eligible, starts) = collect("/* @js_moving keep-order */ f(); g();", usize::MAX);
+        assert!(eligible.keep_order(Reach::List, starts[0]));
+        assert!(!eligible.keep_order(Reach::Node, starts[0]));
+    }
+
+    #[test]
+    fn literals_follow_depth_and_pragmas() {
+        let source = "const o = {a: {b: {c: 1}, d: /* @js_moving unordered */ {e: {f: 1}}}, g: /* @js_moving keep-order */ {h: 1}};";
+        let (_, module, comments) = crate::parse_source_with_comments(source);
+        let eligible = super::collect(&module, &comments, 2, &[]);
+        let ModuleItem::Stmt(Stmt::Decl(Decl::Var(var))) = &module.body[0] else { unreachable!() };
+        let mut starts = vec![];
+        collect_literals(var.decls[0].init.as_deref().unwrap(), &mut starts);
+        let eligible: Vec<bool> = starts.iter().map(|lo| eligible.literal_at(*lo)).collect();
+        // o, a and d (marked) are, b and e (too deep) and g (kept) aren't
+        assert_eq!(eligible, vec![true, true, false, true, false, false]);
+    }
+
+    // Starts of the literals in `expr`, outer before inner.
+    fn collect_literals(expr: &Expr, starts: &mut Vec<u32>) {
+        if let Expr::Object(lit) = expr {
+            starts.push(lit.span.lo.0);
+            for prop in &lit.props {
+                if let Some(value) = prop.as_prop().and_then(|prop| prop.as_key_value()) {
+                    collect_literals(&value.value, starts);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn kept_arrays_arent_eligible() {
+        let source = "const a = /* @js_moving unordered */ [1, 2, 3], b = /* @js_moving keep-order */ [1, 2, 3];";
+        let (_, module, comments) = crate::parse_source_with_comments(source);
+        let eligible = super::collect(&module, &comments, usize::MAX, &[]);
+        let ModuleItem::Stmt(Stmt::Decl(Decl::Var(var))) = &module.body[0] else { unreachable!() };
+        let arrays: Vec<bool> = var
+            .decls
+            .iter()
+            .map(|decl| match decl.init.as_deref() {
+                Some(Expr::Array(arr)) => eligible.array(arr),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(arrays, vec![true, false]);
+    }
+}
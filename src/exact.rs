@@ -0,0 +1,81 @@
+use crate::budget::Budget;
+use crate::problem::Problem;
+
+/// Tries every ordering of the problem's units that keeps to `before` and
+/// returns the one with the smallest compressed size. Compressed size isn't
+/// additive over adjacent pairs, so there's no DP shortcut that stays exact;
+/// up to n! evaluations is only affordable for a handful of movable units.
+/// Every evaluation counts against `budget`, and if that runs out (or we're
+/// interrupted) this returns the best order seen so far, with
+/// `budget.stop_reason()` saying why.
+pub fn solve(problem: &Problem, budget: &Budget) -> (Vec<usize>, usize) {
+    let n = problem.units.len();
+    let original: Vec<usize> = (0..n).collect();
+    let mut best = (original.clone(), problem.size(&original));
+    extend(problem, budget, &mut Vec::with_capacity(n), &mut vec![false; n], &mut best);
+    best
+}
+
+// Tries every way to finish `order` with the units not yet `placed`, taking
+// each only once everything it has to follow is in.
+fn extend(problem: &Problem, budget: &Budget, order: &mut Vec<usize>, placed: &mut [bool], best: &mut (Vec<usize>, usize)) {
+    if order.len() == placed.len() {
+        let size = problem.size(order);
+        budget.record(size);
+        if size < best.1 {
+            *best = (order.clone(), size);
+        }
+        return;
+    }
+    for unit in 0..placed.len() {
+        if placed[unit] || !problem.before.get(unit).is_none_or(|before| before.iter().all(|before| placed[*before])) {
+            continue;
+        }
+        if budget.exhausted() {
+            return;
+        }
+        placed[unit] = true;
+        order.push(unit);
+        extend(problem, budget, order, placed, best);
+        order.pop();
+        placed[unit] = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::budget::{Budget, Limits};
+    use crate::problem::Problem;
+
+    fn limits(evaluations: Option<u64>) -> Limits {
+        Limits { time: Duration::from_secs(60), evaluations, patience: None, min_bytes_per_minute: None }
+    }
+
+    fn problem(before: Vec<Vec<usize>>) -> Problem {
+        let units = ["abcabc", "xyz", "abcabd", "xyw"].iter().map(|unit| unit.as_bytes().to_vec()).collect();
+        Problem { prefix: vec![], units, separator: b",".to_vec(), suffix: vec![], quality: 11, before }
+    }
+
+    #[test]
+    fn tries_every_order_that_keeps_constraints() {
+        // 0 before 1 before 2, with 3 anywhere
+        let problem = problem(vec![vec![], vec![0], vec![1], vec![]]);
+        let budget = Budget::new(&limits(None), usize::MAX);
+        let (order, size) = super::solve(&problem, &budget);
+        assert_eq!(budget.stop_reason(), None);
+        assert_eq!(budget.evaluations(), 4);
+        assert_eq!(problem.repair(&order), order);
+        assert_eq!(problem.size(&order), size);
+    }
+
+    #[test]
+    fn stops_when_the_budget_runs_out() {
+        let problem = problem(vec![]);
+        let budget = Budget::new(&limits(Some(5)), usize::MAX);
+        super::solve(&problem, &budget);
+        assert_eq!(budget.stop_reason(), Some("evaluation budget"));
+        assert_eq!(budget.evaluations(), 5);
+    }
+}
@@ -0,0 +1,52 @@
+use std::ops::Range;
+
+use swc_ecma_ast::{Decl, FnDecl, ModuleItem, Stmt};
+
+/// Runs of two or more function declarations in a row whose order can't be
+/// observed. Declarations are hoisted, so within one statement list they're
+/// all created before any of it runs. In sloppy mode a block-level function
+/// also gets copied out to the enclosing function's var when its declaration
+/// is reached (annex B), but nothing runs between the declarations of one
+/// run, so only which of two same-named declarations wins could change.
+/// Runs with a repeated name are left out for that reason.
+pub fn runs<T>(items: &[T], decl: impl Fn(&T) -> Option<&FnDecl>) -> Vec<Range<usize>> {
+    let mut runs = vec![];
+    let mut start = 0;
+    for end in 0..=items.len() {
+        if end < items.len() && decl(&items[end]).is_some() {
+            continue;
+        }
+        if end - start >= 2 && unique_names(&items[start..end], &decl) {
+            runs.push(start..end);
+        }
+        start = end + 1;
+    }
+    runs
+}
+
+fn unique_names<T>(items: &[T], decl: &impl Fn(&T) -> Option<&FnDecl>) -> bool {
+    let mut names = names(items, decl);
+    names.sort();
+    names.windows(2).all(|pair| pair[0] != pair[1])
+}
+
+/// The declared names, in order, of a run found by `runs`.
+pub fn names<T>(items: &[T], decl: impl Fn(&T) -> Option<&FnDecl>) -> Vec<String> {
+    items.iter().map(|item| decl(item).unwrap().ident.sym.to_string()).collect()
+}
+
+pub fn stmt_decl(stmt: &Stmt) -> Option<&FnDecl> {
+    match stmt {
+        Stmt::Decl(Decl::Fn(decl)) => Some(decl),
+        _ => None,
+    }
+}
+
+// Exported functions are module declarations, not statements, so they're
+// never part of a run.
+pub fn item_decl(item: &ModuleItem) -> Option<&FnDecl> {
+    match item {
+        ModuleItem::Stmt(stmt) => stmt_decl(stmt),
+        ModuleItem::ModuleDecl(_) => None,
+    }
+}
@@ -0,0 +1,86 @@
+use std::ops::Range;
+
+use globset::GlobSet;
+use swc_ecma_ast::{ImportDecl, ModuleDecl, ModuleItem};
+
+use crate::problem;
+
+/// Runs of two or more import declarations in a row. Imported modules are
+/// evaluated in the order they're imported, so only a run's side-effect-free
+/// imports can trade places; see `constraints`.
+pub fn runs(items: &[ModuleItem]) -> Vec<Range<usize>> {
+    let mut runs = vec![];
+    let mut start = 0;
+    for end in 0..=items.len() {
+        if end < items.len() && import(&items[end]).is_some() {
+            continue;
+        }
+        if end - start >= 2 {
+            runs.push(start..end);
+        }
+        start = end + 1;
+    }
+    runs
+}
+
+/// What has to stay ahead of what in a run, the way `Problem::before` takes
+/// it: imports of modules matching `side_effect_free` can go in any order
+/// among themselves, everything else stays where it is. Bare `import "x"`
+/// only exists for its side effects, so it stays put whatever it imports.
+/// None if no two imports can trade places.
+pub fn constraints(run: &[ModuleItem], side_effect_free: &GlobSet) -> Option<Vec<Vec<usize>>> {
+    let free: Vec<bool> = run
+        .iter()
+        .map(|item| {
+            let decl = import(item).unwrap();
+            !decl.specifiers.is_empty() && side_effect_free.is_match(&*decl.src.value)
+        })
+        .collect();
+    let before = problem::free_constraints(&free);
+    if problem::forced(&before) {
+        return None;
+    }
+    Some(before)
+}
+
+/// A label per import for manifests: the module it imports.
+pub fn keys(run: &[ModuleItem]) -> Vec<String> {
+    run.iter().map(|item| import(item).unwrap().src.value.to_string()).collect()
+}
+
+fn import(item: &ModuleItem) -> Option<&ImportDecl> {
+    match item {
+        ModuleItem::ModuleDecl(ModuleDecl::Import(decl)) => Some(decl),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::batch::glob_set;
+
+    fn constraints(source: &str) -> Option<Vec<Vec<usize>>> {
+        let module = crate::parse_source(source);
+        let runs = super::runs(&module.body);
+        assert_eq!(runs.len(), 1, "expected one run in {}", source);
+        super::constraints(&module.body[runs[0].clone()], &glob_set(&["pure-*".to_string()]))
+    }
+
+    #[test]
+    fn runs_are_two_or_more_imports_in_a_row() {
+        let module = crate::parse_source("import a from 'x'; import b from 'y'; f(); import c from 'z'; import d from 'w'; import e from 'v';");
+        assert_eq!(super::runs(&module.body), vec![0..2, 3..6]);
+        assert_eq!(super::keys(&module.body[3..6]), vec!["z", "w", "v"]);
+        assert!(super::runs(&crate::parse_source("import a from 'x'; f();").body).is_empty());
+    }
+
+    #[test]
+    fn only_side_effect_free_imports_move() {
+        // other modules' side effects pin them, and so does a bare import,
+        // which is only there for its own
+        let before = constraints("import a from 'pure-a'; import b from 'pure-b'; import c from 'other'; import 'pure-c'; import d from 'pure-d';");
+        assert_eq!(before, Some(vec![vec![], vec![], vec![0, 1], vec![0, 1, 2], vec![2, 3]]));
+        assert_eq!(constraints("import a from 'pure-a'; import b from 'other';"), None);
+        assert_eq!(constraints("import 'pure-a'; import 'pure-b';"), None);
+    }
+}
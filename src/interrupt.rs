@@ -0,0 +1,20 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// On the first SIGINT/SIGTERM, asks every search to wind down so the best
+/// result so far still gets written. A second one exits immediately.
+pub fn install() {
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::AcqRel) {
+            eprintln!("Interrupted again, exiting without writing anything");
+            std::process::exit(130);
+        }
+        eprintln!("Interrupted, finishing up with the best result so far (interrupt again to force exit)");
+    })
+    .expect("failed to install signal handler");
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Acquire)
+}
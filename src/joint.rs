@@ -0,0 +1,216 @@
+use std::time::{Duration, Instant};
+
+use swc_common::sync::Lrc;
+use swc_common::{SourceMap, DUMMY_SP};
+use swc_ecma_ast::{Ident, Module, ModuleItem, ObjectLit, Prop, PropOrSpread};
+use swc_ecma_visit::{VisitMut, VisitMutWith};
+
+use crate::budget::Limits;
+use crate::eligible::Eligible;
+use crate::manifest::Manifest;
+use crate::options::Options;
+use crate::portfolio::{self, Best, Search};
+use crate::problem::{self, Problem};
+
+// stands in for a literal's props so the emitted file can be cut around them
+const SENTINEL: &str = "__js_moving_joint_sentinel__";
+// the budget gets split as if descent takes this many rounds to settle
+const EXPECTED_ROUNDS: usize = 3;
+
+/// Coordinate descent over the whole file: reorders one literal at a time,
+/// measuring the compressed size of the entire file rather than the literal
+/// on its own, since brotli's window lets literals share context. Goes round
+/// all literals until a full round improves none of them or `budget` runs
+/// out. Manifest entries are updated to the orders it settles on.
+pub fn run(
+    module: &mut Module,
+    cm: &Lrc<SourceMap>,
+    eligible: &Eligible,
+    options: &Options,
+    budget: Duration,
+    rng_seed: u64,
+    manifest: &mut Manifest,
+) {
+    let whole = crate::ast_bytes(module, cm);
+    if contains(&whole, SENTINEL.as_bytes()) {
+        println!("File already contains {}, skipping joint optimization", SENTINEL);
+        return;
+    }
+    // the same literals as in the first pass, by where they start since
+    // reordering one moves the literals nested in it around
+    let literals: Vec<u32> = literal_starts(module).into_iter().filter(|lo| eligible.literal_at(*lo)).collect();
+    let slice = budget / (literals.len() * EXPECTED_ROUNDS).max(1) as u32;
+    let start = Instant::now();
+    let mut size = problem::compressed_len(&whole, options.quality);
+    println!("Joint optimization of {} literals starting from {} bytes", literals.len(), size);
+
+    'rounds: for round in 1.. {
+        let mut improved = 0;
+        for &target in &literals {
+            let remaining = budget.saturating_sub(start.elapsed());
+            if remaining.is_zero() || crate::interrupt::interrupted() {
+                break 'rounds;
+            }
+            let Some(problem) = joint_problem(module, cm, target, options.quality) else {
+                continue;
+            };
+            // the file as it stands, i.e. the literal in its current order
+            let current: Vec<usize> = (0..problem.units.len()).collect();
+            let current_size = problem.size(&current);
+            let limits = Limits { time: slice.min(remaining), ..options.limits.clone() };
+            let search = Search::new(&problem, Best { order: current, size: current_size, found_by: "current" }, &limits);
+            let seed = rng_seed ^ ((round as u64) << 32) ^ target as u64;
+            portfolio::run(&search, &options.strategies, options.threads, seed, |_| {});
+
+            let best = search.best();
+            if best.size >= current_size {
+                continue;
+            }
+            // strategies search freely and only get measured repaired, so
+            // the best order is only good as its repaired version
+            let order = problem.repair(&best.order);
+            with_literal(module, target, |path, lit| {
+                crate::apply_order(&mut lit.props, &order);
+                println!("Round {}: literal {} {} -> {} bytes of file, {} won", round, path, current_size, best.size, best.found_by);
+                // paths were numbered before function runs and statements
+                // moved module items around, spans still match
+                let span = (lit.span.lo.0, lit.span.hi.0);
+                if let Some(entry) = manifest.literals.iter_mut().find(|entry| entry.span == span) {
+                    entry.order = order.iter().map(|i| entry.order[*i]).collect();
+                    entry.size = crate::ast_bytes_compressed(&*lit, cm, options.quality);
+                }
+            });
+            improved += 1;
+            size = best.size;
+        }
+        println!("Round {} improved {} literals, file is {} bytes", round, improved, size);
+        if improved == 0 {
+            break;
+        }
+    }
+}
+
+// The whole file cut around the target literal's props, so an order's size
+// is the file's compressed size with the literal laid out that way. None if
+// there's nothing to reorder, or if the emitter doesn't lay the literal out
+// the way rendering would, in which case sizes measured here would be off.
+fn joint_problem(module: &mut Module, cm: &Lrc<SourceMap>, target: u32, quality: u32) -> Option<Problem> {
+    let whole = crate::ast_bytes(module, cm);
+    let mut props = vec![];
+    with_literal(module, target, |_, lit| {
+        let sentinel = PropOrSpread::Prop(Box::new(Prop::Shorthand(Ident::new(SENTINEL.into(), DUMMY_SP))));
+        props = std::mem::replace(&mut lit.props, vec![sentinel]);
+    });
+    let marked = crate::ast_bytes(module, cm);
+    let units: Vec<Vec<u8>> = props.iter().map(|prop| crate::ast_bytes(prop, cm)).collect();
+    let before = crate::props::constraints(&props);
+    with_literal(module, target, |_, lit| lit.props = std::mem::take(&mut props));
+    if units.len() < 2 {
+        return None;
+    }
+
+    let position = marked.windows(SENTINEL.len()).position(|window| window == SENTINEL.as_bytes())?;
+    let problem = Problem {
+        prefix: marked[..position].to_vec(),
+        units,
+        separator: b",".to_vec(),
+        suffix: marked[position + SENTINEL.len()..].to_vec(),
+        quality,
+        before,
+    };
+    if problem.render(&(0..problem.units.len()).collect::<Vec<_>>()) != whole {
+        println!("Literal at {} doesn't render back to the file, leaving it alone", target);
+        return None;
+    }
+    Some(problem)
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|window| window == needle)
+}
+
+// Walks literals the same way `Compressor` does so paths line up, handing
+// the one starting at `target` to `f` along with its path. Also notes where
+// every literal starts.
+struct AtLiteral<F> {
+    target: u32,
+    location: (usize, usize),
+    starts: Vec<u32>,
+    f: F,
+}
+
+impl<F: FnMut(&str, &mut ObjectLit)> VisitMut for AtLiteral<F> {
+    fn visit_mut_module_items(&mut self, items: &mut Vec<ModuleItem>) {
+        for (i, item) in items.iter_mut().enumerate() {
+            self.location = (i, 0);
+            item.visit_mut_with(self);
+        }
+    }
+
+    fn visit_mut_object_lit(&mut self, lit: &mut ObjectLit) {
+        let path = format!("{}/{}", self.location.0, self.location.1);
+        self.location.1 += 1;
+        self.starts.push(lit.span.lo.0);
+        lit.visit_mut_children_with(self);
+        if lit.span.lo.0 == self.target {
+            (self.f)(&path, lit);
+        }
+    }
+}
+
+fn with_literal(module: &mut Module, target: u32, f: impl FnMut(&str, &mut ObjectLit)) {
+    module.visit_mut_with(&mut AtLiteral { target, location: (0, 0), starts: vec![], f });
+}
+
+// Where every literal in the module starts.
+fn literal_starts(module: &mut Module) -> Vec<u32> {
+    let f = |_: &str, _: &mut ObjectLit| {};
+    let mut visitor = AtLiteral { target: u32::MAX, location: (0, 0), starts: vec![], f };
+    module.visit_mut_with(&mut visitor);
+    visitor.starts
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::budget::Limits;
+    use crate::eligible;
+    use crate::manifest::Manifest;
+    use crate::options::Options;
+
+    // Where each of `needles` is in `haystack`.
+    fn positions(haystack: &str, needles: &[&str]) -> Vec<usize> {
+        needles.iter().map(|needle| haystack.find(needle).unwrap()).collect()
+    }
+
+    #[test]
+    fn applied_orders_keep_props_constraints() {
+        let source = r#"const base = {k0: 0};
+const o = {k1: "aaaaaa", k2: "bbbbbb", k5: 1, k3: "aaaaaa", ...base, k4: "bbbbbb", k5: 2, k6: "aaaaaa", k7: "bbbbbb"};"#;
+        let (cm, mut module, comments) = crate::parse_source_with_comments(source);
+        let eligible = eligible::collect(&module, &comments, usize::MAX, &[]);
+        let limits = Limits { time: Duration::from_millis(50), evaluations: None, patience: None, min_bytes_per_minute: None };
+        let options = Options { threads: 1, limits, ..Options::default() };
+        for seed in 0..3 {
+            super::run(&mut module, &cm, &eligible, &options, Duration::from_millis(300), seed, &mut Manifest::default());
+        }
+        let output = String::from_utf8(crate::ast_bytes(&module, &cm)).unwrap();
+        let [spread, first, second] = positions(&output, &["...base", "k5:1", "k5:2"])[..] else { unreachable!() };
+        assert!(first < second, "{}", output);
+        assert!(positions(&output, &["k1:", "k2:", "k3:"]).iter().all(|at| *at < spread), "{}", output);
+        assert!(positions(&output, &["k4:", "k6:", "k7:"]).iter().all(|at| *at > spread), "{}", output);
+    }
+
+    #[test]
+    fn literals_are_found_where_they_started_after_reordering() {
+        let (cm, mut module, _) = crate::parse_source_with_comments("const o = {a: {x: 1}, b: {y: 2}, c: {z: 3}};");
+        let starts = super::literal_starts(&mut module);
+        super::with_literal(&mut module, starts[0], |_, lit| crate::apply_order(&mut lit.props, &[2, 1, 0]));
+        for (start, key) in starts[1..].iter().zip(["x", "y", "z"]) {
+            let mut found = String::new();
+            super::with_literal(&mut module, *start, |_, lit| found = String::from_utf8(crate::ast_bytes(&*lit, &cm)).unwrap());
+            assert!(found.starts_with(&format!("{{{}:", key)), "{} at {}", found, start);
+        }
+    }
+}
@@ -1,155 +1,1099 @@
-use std::collections::HashMap;
-use std::{io::Read};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-use simhash::hamming_distance;
+use globset::GlobSet;
+use swc_common::comments::{Comments, SingleThreadedComments};
 use swc_common::sync::Lrc;
-use swc_common::{SourceMap};
-use swc_ecma_ast::{Stmt, ObjectLit};
+use swc_common::source_map::SourceMapGenConfig;
+use swc_common::{BytePos, FileName, LineCol, SourceFile, SourceMap};
+use swc_ecma_ast::{ArrayLit, Class, FnDecl, ImportDecl, Module, ModuleItem, NamedExport, ObjectLit, ObjectPat, Stmt, SwitchStmt, VarDecl};
 use swc_ecma_parser::{lexer::Lexer, Parser, StringInput, Syntax};
-use swc_ecma_visit::VisitMut;
+use swc_ecma_visit::{Visit, VisitMut, VisitMutWith, VisitWith};
 use swc_common::Spanned;
 
-use rand::thread_rng;
-use rand::Rng;
-use rand::prelude::SliceRandom;
-
+mod allocation;
+mod arrays;
+mod baselines;
+mod batch;
+mod budget;
+mod cache;
+mod checkpoint;
+mod comments;
+mod declarators;
+mod eligible;
+mod exact;
+mod functions;
+mod imports;
+mod interrupt;
+mod joint;
+mod manifest;
+mod members;
+mod options;
+mod patterns;
+mod portfolio;
+mod pragmas;
+mod problem;
+mod props;
+mod purity;
+mod specifiers;
+mod statements;
+mod strategies;
+mod switches;
 mod traveling_salesman;
+mod warm_start;
+
+use cache::Cache;
+use checkpoint::{Checkpoint, LiteralState};
+use eligible::{Eligible, Reach};
+use manifest::Manifest;
+use options::{Mode, Options};
+use problem::Problem;
 
-fn getHiLo(node: impl Spanned) -> (u32, u32) {
+fn get_hi_lo(node: impl Spanned) -> (u32, u32) {
     let span = node.span();
     (span.lo.0, span.hi.0)
 }
 
 struct Compressor<'a> {
-    // (u32, u32) char span -> 64 bit hash
-    simhashMap: std::collections::HashMap<(u32, u32), u64>,
-    // 64 bit hash -> (u32, u32) char span
-    reverseSimhashes: std::collections::HashMap<u64, (u32, u32)>,
-    simhashes: Vec<u64>,
     cm: &'a Lrc<SourceMap>,
+    options: Options,
+    stats: Stats,
+    // progress so far, saved to the checkpoint file if there is one
+    checkpoint: Checkpoint,
+    // when the checkpoint file was last written
+    last_checkpoint: Instant,
+    // orders picked so far, written out as a manifest at the end
+    manifest: Manifest,
+    // in apply mode, the manifest to take orders from instead of searching
+    applying: Option<Manifest>,
+    // orders from a previous build to start searching from
+    warm_start: Option<Manifest>,
+    cache: Option<Cache>,
+    // current module item index and how many literals we've seen in it
+    location: (usize, usize),
+    // how many literals deep we are, 1 inside an outermost one
+    depth: usize,
+    // with a per-file budget: the time left for it and the serialized size
+    // of the literals still to be searched, which it gets split across
+    time_share: Option<(Duration, usize)>,
+    // with --allocate, results of the shared search run before visiting,
+    // for the literals it covered
+    allocated: Vec<LiteralState>,
+    // --statements, unless the file uses eval or with
+    reorder_statements: bool,
+    // unless the file uses eval or with
+    reorder_declarators: bool,
+    // --side-effect-free modules, whose imports can be reordered
+    side_effect_free: Option<GlobSet>,
+    // which literals and arrays get reordered, and what pragmas keep
+    eligible: &'a Eligible,
+}
 
-    runsWithNoChanges: u32,
+// Something whose parts can be reordered: an object literal's props, a run
+// of function declarations, a statement list, a class body, the names in an
+// import or export, a run of imports, a destructuring pattern, the
+// declarators of a var, let or const, a switch's cases or the elements of an
+// array marked unordered.
+struct Target {
+    kind: TargetKind,
+    span: (u32, u32),
+    path: String,
+    // a label per part, for manifests and warm starts
+    keys: Vec<String>,
+    // cheap deterministic orders to start searching from
+    candidates: Vec<(&'static str, Vec<usize>)>,
+    // without units when applying a manifest, only `before` is used then
+    problem: Problem,
+}
 
-    distanceMatrix: Vec<Vec<f64>>,
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TargetKind {
+    Literal,
+    Functions,
+    Statements,
+    Members,
+    Specifiers,
+    Imports,
+    Pattern,
+    Declarators,
+    Cases,
+    Elements,
 }
 
-fn window_distance(simhashes: &Vec<u64>, index: usize, window: usize) -> u32 {
-    let mut total_distance = 0;
-    let index_simhash = simhashes[index];
-    let min_value = std::cmp::max((index as isize) - (window as isize), 0) as usize;
-    let max_value = std::cmp::min(index + window, simhashes.len());
-    for i in min_value..max_value {
-        total_distance += hamming_distance(simhashes[i], index_simhash);
+impl TargetKind {
+    fn reach(self) -> Reach {
+        match self {
+            TargetKind::Functions | TargetKind::Imports => Reach::Run,
+            TargetKind::Statements => Reach::List,
+            _ => Reach::Node,
+        }
+    }
+
+    // What its parts are called, which also tells kinds apart in checkpoints.
+    fn name(self) -> &'static str {
+        match self {
+            TargetKind::Literal => "props",
+            TargetKind::Functions => "functions",
+            TargetKind::Statements => "statements",
+            TargetKind::Members => "members",
+            TargetKind::Specifiers => "specifiers",
+            TargetKind::Imports => "imports",
+            TargetKind::Pattern => "bindings",
+            TargetKind::Declarators => "declarators",
+            TargetKind::Cases => "cases",
+            TargetKind::Elements => "elements",
+        }
     }
-    total_distance
 }
 
-impl<'a> VisitMut for Compressor<'a> {
-    fn visit_mut_object_lit(&mut self, lit: &mut ObjectLit) {
-        if self.simhashMap.len() == 0 {
-            println!("Calculating simhashes");
-            for prop in &lit.props {
-                let buf = ast_bytes(prop, self.cm);
-                let str = String::from_utf8(buf).expect("invalid utf8 character detected");
-                let hashed = simhash::simhash(&str);
-                self.simhashMap.insert(getHiLo(prop), hashed);
-                self.reverseSimhashes.insert(hashed, getHiLo(prop));
-                self.simhashes.push(hashed);
-            }
+impl Target {
+    fn describe(&self) -> String {
+        format!("{} at {}..{} with {} {}", self.path, self.span.0, self.span.1, self.keys.len(), self.kind.name())
+    }
+}
+
+#[derive(Default)]
+struct Stats {
+    literals: usize,
+    function_runs: usize,
+    statement_lists: usize,
+    class_bodies: usize,
+    specifier_lists: usize,
+    import_runs: usize,
+    patterns: usize,
+    declarations: usize,
+    switches: usize,
+    arrays: usize,
+    // literals and runs left alone because we were interrupted before
+    // reaching them
+    skipped: usize,
+    // literals and runs whose order came straight from the cache
+    cached: usize,
+    // compressed bytes saved, measured on each literal or run on its own
+    bytes_saved: isize,
+    // literals optimized and bytes saved at each nesting depth, from 1
+    levels: Vec<(usize, isize)>,
+}
+
+// Path of a function run: "fn:<first name>" at the top level, otherwise
+// prefixed with the module item it's in. Literal paths count literals, so
+// this keeps runs from shifting them.
+fn function_path(item: Option<usize>, names: &[String]) -> String {
+    match item {
+        Some(item) => format!("{}/fn:{}", item, names[0]),
+        None => format!("fn:{}", names[0]),
+    }
+}
+
+// Likewise for a statement list: "stmts" for the module body.
+fn statements_path(item: Option<usize>, keys: &[String]) -> String {
+    match item {
+        Some(item) => format!("{}/stmts:{}", item, keys[0]),
+        None => "stmts".to_string(),
+    }
+}
+
+// Imports are only ever at the top level: "imports:<first module>".
+fn imports_path(keys: &[String]) -> String {
+    format!("imports:{}", keys[0])
+}
+
+// And a class body, which is always inside some module item.
+fn class_path(item: usize, keys: &[String]) -> String {
+    format!("{}/class:{}", item, keys[0])
+}
+
+// Destructuring patterns too, though they can't be in an import.
+fn pattern_path(item: usize, keys: &[String]) -> String {
+    format!("{}/pattern:{}", item, keys[0])
+}
+
+fn declarators_path(item: usize, keys: &[String]) -> String {
+    format!("{}/var:{}", item, keys[0])
+}
+
+fn switch_path(item: usize, keys: &[String]) -> String {
+    format!("{}/switch:{}", item, keys[0])
+}
+
+fn array_path(item: usize, keys: &[String]) -> String {
+    format!("{}/array:{}", item, keys[0])
+}
+
+impl<'a> Compressor<'a> {
+    // Serialized parts, for measuring orders. Applying a manifest measures
+    // nothing, so there it's left empty, as are candidates.
+    fn units<T>(&self, items: &[T], bytes: impl Fn(&T) -> Vec<u8>) -> Vec<Vec<u8>> {
+        if self.applying.is_some() {
+            return vec![];
+        }
+        items.iter().map(bytes).collect()
+    }
+
+    fn candidates(&self, candidates: impl FnOnce() -> Vec<(&'static str, Vec<usize>)>) -> Vec<(&'static str, Vec<usize>)> {
+        if self.applying.is_some() {
+            return vec![];
+        }
+        candidates()
+    }
+
+    fn object_target(&self, lit: &ObjectLit, path: String) -> Target {
+        let units = self.units(&lit.props, |prop| ast_bytes(prop, self.cm));
+        Target {
+            kind: TargetKind::Literal,
+            span: get_hi_lo(lit),
+            path,
+            keys: manifest::prop_keys(&lit.props, self.cm),
+            candidates: self.candidates(|| baselines::candidates(&lit.props, &units, self.cm)),
+            problem: Problem {
+                prefix: b"{".to_vec(),
+                units,
+                separator: b",".to_vec(),
+                suffix: b"}".to_vec(),
+                quality: self.options.quality,
+                before: props::constraints(&lit.props),
+            },
+        }
+    }
+
+    // `items` is a run found by `functions::runs`. Minified function
+    // declarations need nothing between them.
+    fn function_target<T: swc_ecma_codegen::Node + Spanned>(&self, items: &[T], names: Vec<String>, path: String) -> Target {
+        let units = self.units(items, |item| ast_bytes(item, self.cm));
+        Target {
+            kind: TargetKind::Functions,
+            span: (items[0].span().lo.0, items[items.len() - 1].span().hi.0),
+            path,
+            candidates: self.candidates(|| baselines::labelled_candidates(&names, &units)),
+            keys: names,
+            problem: Problem {
+                prefix: vec![],
+                units,
+                separator: vec![],
+                suffix: vec![],
+                quality: self.options.quality,
+                before: vec![],
+            },
+        }
+    }
+
+    // Units keep their semicolons, so they just get concatenated.
+    fn statements_target<T: swc_ecma_codegen::Node + Spanned>(
+        &self,
+        items: &[T],
+        before: Vec<Vec<usize>>,
+        keys: Vec<String>,
+        path: String,
+    ) -> Target {
+        let units = self.units(items, |item| statement_bytes(item, self.cm));
+        Target {
+            kind: TargetKind::Statements,
+            span: (items[0].span().lo.0, items[items.len() - 1].span().hi.0),
+            path,
+            candidates: self.candidates(|| baselines::labelled_candidates(&keys, &units)),
+            keys,
+            problem: Problem { prefix: vec![], units, separator: vec![], suffix: vec![], quality: self.options.quality, before },
+        }
+    }
+
+    // Members keep their semicolons (fields need them), like statements.
+    fn class_target(&self, class: &Class, before: Vec<Vec<usize>>, path: String) -> Target {
+        let units = self.units(&class.body, |member| statement_bytes(member, self.cm));
+        let keys = members::keys(&class.body, self.cm);
+        Target {
+            kind: TargetKind::Members,
+            span: get_hi_lo(class),
+            path,
+            candidates: self.candidates(|| baselines::labelled_candidates(&keys, &units)),
+            keys,
+            problem: Problem { prefix: vec![], units, separator: vec![], suffix: vec![], quality: self.options.quality, before },
+        }
+    }
+
+    // Small enough targets get every order tried, nothing can beat that.
+    // `pat` has passed `patterns::reorderable`.
+    fn pattern_target(&self, pat: &ObjectPat, item: usize) -> Target {
+        let units = self.units(&pat.props, |prop| ast_bytes(prop, self.cm));
+        let keys = patterns::keys(pat);
+        Target {
+            kind: TargetKind::Pattern,
+            span: get_hi_lo(pat),
+            path: pattern_path(item, &keys),
+            candidates: self.candidates(|| baselines::labelled_candidates(&keys, &units)),
+            keys,
+            problem: Problem {
+                prefix: b"{".to_vec(),
+                units,
+                separator: b",".to_vec(),
+                suffix: b"}".to_vec(),
+                quality: self.options.quality,
+                before: vec![],
+            },
+        }
+    }
+
+    // `var` has passed `declarators::reorderable`.
+    fn declarators_target(&self, var: &VarDecl, item: usize) -> Target {
+        let units = self.units(&var.decls, |decl| ast_bytes(decl, self.cm));
+        let keys = declarators::keys(var);
+        Target {
+            kind: TargetKind::Declarators,
+            span: get_hi_lo(var),
+            path: declarators_path(item, &keys),
+            candidates: self.candidates(|| baselines::labelled_candidates(&keys, &units)),
+            keys,
+            problem: Problem {
+                prefix: format!("{} ", var.kind).into_bytes(),
+                units,
+                separator: b",".to_vec(),
+                suffix: vec![],
+                quality: self.options.quality,
+                before: vec![],
+            },
+        }
+    }
+
+    // Cases end in statements, so like those they keep their semicolons.
+    fn switch_target(&self, switch: &SwitchStmt, before: Vec<Vec<usize>>, item: usize) -> Target {
+        let units = self.units(&switch.cases, |case| statement_bytes(case, self.cm));
+        let keys = switches::keys(&switch.cases);
+        Target {
+            kind: TargetKind::Cases,
+            span: get_hi_lo(switch),
+            path: switch_path(item, &keys),
+            candidates: self.candidates(|| baselines::labelled_candidates(&keys, &units)),
+            keys,
+            problem: Problem { prefix: vec![], units, separator: vec![], suffix: vec![], quality: self.options.quality, before },
+        }
+    }
+
+    // Holes are empty units, so the commas around them still add up.
+    fn array_target(&self, arr: &ArrayLit, before: Vec<Vec<usize>>, item: usize) -> Target {
+        let units = self.units(&arr.elems, |elem| elem.as_ref().map_or_else(Vec::new, |elem| ast_bytes(elem, self.cm)));
+        let keys = arrays::keys(arr, self.cm);
+        Target {
+            kind: TargetKind::Elements,
+            span: get_hi_lo(arr),
+            path: array_path(item, &keys),
+            candidates: self.candidates(|| baselines::labelled_candidates(&keys, &units)),
+            keys,
+            problem: Problem {
+                prefix: b"[".to_vec(),
+                units,
+                separator: b",".to_vec(),
+                suffix: b"]".to_vec(),
+                quality: self.options.quality,
+                before,
+            },
+        }
+    }
+
+    // Imports get measured like any other statements.
+    fn imports_target(&self, run: &[ModuleItem], before: Vec<Vec<usize>>) -> Target {
+        let keys = imports::keys(run);
+        let path = imports_path(&keys);
+        Target { kind: TargetKind::Imports, ..self.statements_target(run, before, keys, path) }
+    }
+
+    // The named specifiers of `import{a,b as c}from"x"` or `export{a,b}`,
+    // which sit between braces like props and can go in any order.
+    fn specifiers_target(&self, units: Vec<Vec<u8>>, keys: Vec<String>, span: (u32, u32), path: String) -> Target {
+        Target {
+            kind: TargetKind::Specifiers,
+            span,
+            path,
+            candidates: self.candidates(|| baselines::labelled_candidates(&keys, &units)),
+            keys,
+            problem: Problem {
+                prefix: b"{".to_vec(),
+                units,
+                separator: b",".to_vec(),
+                suffix: b"}".to_vec(),
+                quality: self.options.quality,
+                before: vec![],
+            },
+        }
+    }
+
+    fn import_target(&self, decl: &ImportDecl, item: usize) -> Target {
+        let specifiers = &decl.specifiers[specifiers::imported(decl)];
+        let units = self.units(specifiers, |spec| ast_bytes(specifiers::named(spec), self.cm));
+        self.specifiers_target(units, specifiers::import_keys(specifiers), get_hi_lo(decl), format!("{}/import", item))
+    }
+
+    fn export_target(&self, export: &NamedExport, item: usize) -> Target {
+        let specifiers = &export.specifiers[specifiers::exported(export)];
+        let units = self.units(specifiers, |spec| ast_bytes(spec, self.cm));
+        self.specifiers_target(units, specifiers::export_keys(specifiers), get_hi_lo(export), format!("{}/export", item))
+    }
+
+    // Falls back to searching if trying every order doesn't fit the budget.
+    fn solve_exactly(&mut self, target: &Target) -> (Vec<usize>, usize) {
+        let problem = &target.problem;
+        // patience and savings rate don't mean anything when going through
+        // every order, but time (what's left of the file's, if split) and
+        // evaluations do
+        let mut limits = budget::Limits { patience: None, min_bytes_per_minute: None, ..self.options.limits.clone() };
+        if let Some((remaining, _)) = self.time_share {
+            limits.time = limits.time.min(remaining);
+        }
+        let budget = budget::Budget::new(&limits, usize::MAX);
+        let (order, size) = exact::solve(problem, &budget);
+        if let Some((remaining, _)) = &mut self.time_share {
+            *remaining = remaining.saturating_sub(budget.elapsed());
+        }
+        if let Some(reason) = budget.stop_reason().filter(|reason| *reason != "interrupted") {
+            println!("Ran out of {} solving {} exactly, searching instead", reason, target.describe());
+            return self.search(target);
+        }
+        let original: Vec<usize> = (0..order.len()).collect();
+        let original_size = problem.size(&original);
+        println!("Solved {} exactly, {} -> {} bytes", target.describe(), original_size, size);
+        self.record(target, original_size, size);
+        self.finish(LiteralState {
+            kind: target.kind.name().to_string(),
+            span: target.span,
+            order: order.clone(),
+            size,
+            found_by: "exact".to_string(),
+            evaluations: budget.evaluations(),
+            elapsed_secs: budget.elapsed().as_secs_f64(),
+            finished: !interrupt::interrupted(),
+            strategies: vec![],
+        });
+        (order, size)
+    }
 
-            let simhash_len = self.simhashes.len();
-            self.distanceMatrix = Vec::with_capacity(simhash_len);
-            for columnHash in &self.simhashes {
-                let mut array = Vec::with_capacity(simhash_len);
-                for rowHash in &self.simhashes {
-                    array.push(simhash::hamming_distance(*columnHash, *rowHash) as f64);
+    // Where searching starts: the cheapest of the deterministic orders (and
+    // the previous build's order, if we have one), or for a target that was
+    // mid-search in the checkpoint we resumed from, its best order with
+    // whatever budget it had left. Also returns those limits and what was
+    // already spent.
+    fn seed(&self, target: &Target) -> (portfolio::Best, budget::Limits, (Duration, u64)) {
+        match self.checkpoint.get(target.kind.name(), target.span) {
+            Some(state) => {
+                println!("Resuming {} from checkpoint ({} bytes)", target.describe(), state.size);
+                let elapsed = Duration::from_secs_f64(state.elapsed_secs);
+                (
+                    portfolio::Best { order: state.order.clone(), size: state.size, found_by: "checkpoint" },
+                    self.options.limits.remaining(elapsed, state.evaluations),
+                    (elapsed, state.evaluations),
+                )
+            }
+            None => {
+                let mut candidates = target.candidates.clone();
+                if let Some(previous) = &self.warm_start {
+                    if let Some(order) = warm_start::order(previous, &target.path, &target.keys, &target.problem.units) {
+                        candidates.push(("warm start", order));
+                    }
                 }
-                self.distanceMatrix.push(array);
+                let (name, order, size) = baselines::best(&target.problem, candidates);
+                println!("Seeding search with {} order ({} bytes)", name, size);
+                (portfolio::Best { order, size, found_by: name }, self.options.limits.clone(), (Duration::ZERO, 0))
             }
         }
+    }
+
+    // Races the strategies from the target's seed order, unless the shared
+    // search up front already covered it.
+    fn search(&mut self, target: &Target) -> (Vec<usize>, usize) {
+        let problem = &target.problem;
+        let original: Vec<usize> = (0..problem.units.len()).collect();
+        let original_size = problem.size(&original);
+        let span = target.span;
+
+        if let Some(position) = self.allocated.iter().position(|state| state.is(target.kind.name(), span)) {
+            let state = self.allocated.swap_remove(position);
+            println!("Searched {}: {} won, {} bytes", target.describe(), state.found_by, state.size);
+            self.record(target, original_size, state.size);
+            let (order, size) = (state.order.clone(), state.size);
+            self.finish(state);
+            return (order, size);
+        }
 
-        /*
-        let mut rng = thread_rng();
-        let mut salesman = traveling_salesman::TravellingSalesman{distance_matrix: &self.distanceMatrix, rng: &mut rng};
-        let result = metaheuristics::hill_climbing::solve(&mut salesman,  time::Duration::new(30, 0));
-        println!("got distance {}", traveling_salesman::get_route_distance(&self.distanceMatrix, &result.route));
-        let mut new_props: Vec<swc_ecma_ast::PropOrSpread> = vec![];
-        let mut crap = std::collections::HashMap::new();
-        for i in &lit.props {
-            crap.insert(getHiLo(i), i.clone());
-        }
-        for i in result.route {
-            let hsh = self.simhashes[i];
-            let hshSpan = self.reverseSimhashes.get(&hsh).unwrap();
-            let prp = crap.get(hshSpan).unwrap().clone();
-            new_props.push(prp);
-            // print!("{} ", i);
-        }
-        lit.props = new_props;
-        */
-
-        let mut rng = thread_rng();
-        let num_props = lit.props.len();
-        let mut changes = 0;
-        for _ in 0..100 {
-            let first = rng.gen_range(0..num_props);
-            let second = rng.gen_range(0..num_props);
-
-            let cur_first_distance = window_distance(&self.simhashes, first, 50);
-            let cur_second_distance = window_distance(&self.simhashes, second, 50);
-            let cur_distance = cur_first_distance + cur_second_distance;
-
-            self.simhashes.swap(first, second);
-
-            let new_first_distance = window_distance(&self.simhashes, first, 50);
-            let new_second_distance = window_distance(&self.simhashes, second, 50);
-            let new_distance = new_first_distance + new_second_distance;
-
-            if new_distance < cur_distance {
-                lit.props.swap(first, second);
-                changes += 1;
-            } else {
-                // unswap em
-                self.simhashes.swap(first, second);
+        let (start, mut limits, spent) = self.seed(target);
+
+        let target_bytes = problem.render(&original).len();
+        if let Some((remaining, searchable_bytes)) = self.time_share {
+            // a target that was meant to be solved exactly isn't counted
+            limits.time = remaining.mul_f64((target_bytes as f64 / searchable_bytes.max(1) as f64).min(1.0));
+        }
+
+        // a search resumed from a checkpoint carries on where it was
+        let states = self.checkpoint.get(target.kind.name(), span).map(|state| state.strategies.clone()).unwrap_or_default();
+        let search = portfolio::Search::new(problem, start, &limits).resuming(states);
+        let rng_seed = self.checkpoint.seed ^ ((span.0 as u64) << 32);
+        portfolio::run(&search, &self.options.strategies, self.options.threads, rng_seed, |search| {
+            if let Some(path) = &self.options.checkpoint {
+                if self.last_checkpoint.elapsed() >= self.options.checkpoint_interval {
+                    let mut checkpoint = self.checkpoint.clone();
+                    checkpoint.update(literal_state(target.kind, span, search, spent, false));
+                    checkpoint.save(path);
+                    self.last_checkpoint = Instant::now();
+                }
             }
+        });
+
+        if let Some((remaining, searchable_bytes)) = &mut self.time_share {
+            *remaining = remaining.saturating_sub(search.budget.elapsed());
+            *searchable_bytes = searchable_bytes.saturating_sub(target_bytes);
         }
-        if changes > 0 {
-            self.runsWithNoChanges = 0;
+
+        let best = search.best();
+        println!("Searched {}: {} won, {} bytes", target.describe(), best.found_by, best.size);
+        self.record(target, original_size, best.size);
+        // an interrupted search isn't done, resuming should carry on with it
+        let finished = search.budget.stop_reason() != Some("interrupted");
+        self.finish(literal_state(target.kind, span, &search, spent, finished));
+        (best.order, best.size)
+    }
+
+    fn record(&mut self, target: &Target, original_size: usize, size: usize) {
+        let saved = original_size as isize - size as isize;
+        self.stats.bytes_saved += saved;
+        match target.kind {
+            TargetKind::Functions => self.stats.function_runs += 1,
+            TargetKind::Statements => self.stats.statement_lists += 1,
+            TargetKind::Members => self.stats.class_bodies += 1,
+            TargetKind::Specifiers => self.stats.specifier_lists += 1,
+            TargetKind::Imports => self.stats.import_runs += 1,
+            TargetKind::Pattern => self.stats.patterns += 1,
+            TargetKind::Declarators => self.stats.declarations += 1,
+            TargetKind::Cases => self.stats.switches += 1,
+            TargetKind::Elements => self.stats.arrays += 1,
+            TargetKind::Literal => {}
+        }
+        if target.kind != TargetKind::Literal {
+            return;
+        }
+        self.stats.literals += 1;
+        if self.stats.levels.len() < self.depth {
+            self.stats.levels.resize(self.depth, (0, 0));
+        }
+        let level = &mut self.stats.levels[self.depth - 1];
+        level.0 += 1;
+        level.1 += saved;
+    }
+
+    // Pinned units don't add orders to try, so only the rest count.
+    fn exact(&self, target: &Target) -> bool {
+        target.problem.movable() <= self.options.exact_threshold
+    }
+
+    fn cache_key(&self, target: &Target) -> u64 {
+        let method = if self.exact(target) {
+            "exact".to_string()
         } else {
-            self.runsWithNoChanges += 1;
-        }
-        if self.runsWithNoChanges >= 10 {
-            println!("Shufflin!");
-            self.runsWithNoChanges = 0;
-            lit.props.shuffle(&mut thread_rng());
-            let mut newSimhashes: Vec<u64> = vec![];
-            for prop in &lit.props {
-                let simhashForProp = self.simhashMap.get(&getHiLo(prop)).unwrap();
-                newSimhashes.push(*simhashForProp);
+            self.options.strategies.iter().map(|strategy| strategy.name()).collect::<Vec<_>>().join(",")
+        };
+        Cache::key(&target.problem, &method)
+    }
+
+    fn cached(&self, cache_key: u64, problem: &Problem) -> Option<(Vec<usize>, usize)> {
+        self.cache.as_ref().and_then(|cache| cache.get(cache_key, problem.units.len()))
+    }
+
+    // Picks an order for the target, from the cache if an earlier run
+    // already did the same work.
+    fn optimize(&mut self, target: &Target) -> (Vec<usize>, usize) {
+        let cache_key = self.cache_key(target);
+        if let Some((order, size)) = self.cached(cache_key, &target.problem) {
+            println!("Found {} in cache, {} bytes", target.describe(), size);
+            self.stats.cached += 1;
+            return (order, size);
+        }
+
+        let (order, size) = if self.exact(target) {
+            self.solve_exactly(target)
+        } else {
+            self.search(target)
+        };
+        // an interrupted search could still do better, don't pin it
+        if let (Some(cache), false) = (&self.cache, interrupt::interrupted()) {
+            cache.put(cache_key, &order, size);
+        }
+        (order, size)
+    }
+
+    // Every target in the module that the visit would search rather than
+    // skip, solve exactly or take from the checkpoint or cache.
+    fn searchable(&self, module: &Module) -> Vec<Target> {
+        let mut planner = Planner { compressor: self, targets: vec![], location: (0, 0) };
+        module.visit_with(&mut planner);
+        planner.targets
+    }
+
+    // Splits `budget` between every target that would be searched, by how
+    // much each is turning out to save, and keeps the results for the visit.
+    fn allocate(&mut self, module: &Module, budget: Duration) {
+        let mut arms: Vec<allocation::Arm> = self
+            .searchable(module)
+            .into_iter()
+            .map(|target| {
+                let (best, _, spent) = self.seed(&target);
+                allocation::Arm::new(target.kind.name(), target.span, target.describe(), target.problem, best, spent)
+            })
+            .collect();
+        // time a resumed run already put in counts against the budget
+        let spent: Duration = arms.iter().map(|arm| arm.spent.0).sum();
+
+        let options = self.options.clone();
+        allocation::run(
+            &mut arms,
+            budget.saturating_sub(spent),
+            &options.limits,
+            &options.strategies,
+            options.threads,
+            self.checkpoint.seed,
+            |arm| {
+                self.checkpoint.update(arm.state(false));
+                self.save_checkpoint(false);
+            },
+        );
+        let finished = !interrupt::interrupted();
+        self.allocated = arms.iter().map(|arm| arm.state(finished)).collect();
+    }
+
+    // Applies the manifest's order, or finds and applies the best one.
+    // `items` are the target's parts, in the same order as its units.
+    fn reorder<T: Clone>(&mut self, target: Target, items: &mut [T]) {
+        if self.keep_order(&target) {
+            println!("Keeping {} in its original order", target.describe());
+            return;
+        }
+        if let Some(manifest) = &self.applying {
+            match manifest.find(&target.path, &target.keys).and_then(|entry| entry.order_for(&target.keys)) {
+                // a hand-edited or stale order may break the target's
+                // constraints, which the search never would
+                Some(order) => apply_order(items, &target.problem.repair(&order)),
+                None => println!("No manifest entry for {} with keys {:?}, leaving it alone", target.describe(), target.keys),
+            }
+            return;
+        }
+        // the shared search's results still get applied after an interrupt
+        if interrupt::interrupted() && !self.allocated.iter().any(|state| state.is(target.kind.name(), target.span)) {
+            self.stats.skipped += 1;
+            return;
+        }
+        let (order, size) = match self.checkpoint.get(target.kind.name(), target.span).filter(|state| state.finished) {
+            Some(state) => {
+                println!("Already finished {} in checkpoint, {} bytes", target.describe(), state.size);
+                (state.order.clone(), state.size)
+            }
+            None => self.optimize(&target),
+        };
+        let order = target.problem.repair(&order);
+        self.manifest.literals.push(manifest::Entry { path: target.path, keys: target.keys, order: order.clone(), size, span: target.span });
+        apply_order(items, &order);
+    }
+
+    fn reorder_functions<T: swc_ecma_codegen::Node + Spanned + Clone>(
+        &mut self,
+        items: &mut [T],
+        item: Option<usize>,
+        decl: impl Fn(&T) -> Option<&FnDecl>,
+    ) {
+        for run in functions::runs(items, &decl) {
+            let names = functions::names(&items[run.clone()], &decl);
+            let target = self.function_target(&items[run.clone()], names.clone(), function_path(item, &names));
+            self.reorder(target, &mut items[run]);
+        }
+    }
+
+    fn reorder_statements<T: swc_ecma_codegen::Node + Spanned + Clone>(
+        &mut self,
+        items: &mut [T],
+        item: Option<usize>,
+        stmt: impl Fn(&T) -> Option<&Stmt>,
+    ) {
+        if !self.reorder_statements {
+            return;
+        }
+        if let Some(before) = statements::constraints(items, &stmt) {
+            let keys = statements::keys(items, &stmt, self.cm);
+            let path = statements_path(item, &keys);
+            let target = self.statements_target(items, before, keys, path);
+            self.reorder(target, items);
+        }
+    }
+
+    fn reorder_import_runs(&mut self, items: &mut [ModuleItem]) {
+        let Some(side_effect_free) = &self.side_effect_free else {
+            return;
+        };
+        let runs: Vec<_> = imports::runs(items)
+            .into_iter()
+            .filter_map(|run| Some((imports::constraints(&items[run.clone()], side_effect_free)?, run)))
+            .collect();
+        for (before, run) in runs {
+            let target = self.imports_target(&items[run.clone()], before);
+            self.reorder(target, &mut items[run]);
+        }
+    }
+
+    fn reorder_members(&mut self, class: &mut Class) {
+        if let Some(before) = members::constraints(&class.body) {
+            let path = class_path(self.location.0, &members::keys(&class.body, self.cm));
+            let target = self.class_target(class, before, path);
+            self.reorder(target, &mut class.body);
+        }
+    }
+
+    fn reorder_imports(&mut self, decl: &mut ImportDecl) {
+        let range = specifiers::imported(decl);
+        if range.len() >= 2 {
+            let target = self.import_target(decl, self.location.0);
+            self.reorder(target, &mut decl.specifiers[range]);
+        }
+    }
+
+    fn reorder_exports(&mut self, export: &mut NamedExport) {
+        let range = specifiers::exported(export);
+        if range.len() >= 2 {
+            let target = self.export_target(export, self.location.0);
+            self.reorder(target, &mut export.specifiers[range]);
+        }
+    }
+
+    fn reorder_pattern(&mut self, pat: &mut ObjectPat) {
+        if self.options.trust_getters && patterns::reorderable(pat) {
+            let target = self.pattern_target(pat, self.location.0);
+            self.reorder(target, &mut pat.props);
+        }
+    }
+
+    fn reorder_declarators(&mut self, var: &mut VarDecl) {
+        if self.reorder_declarators && declarators::reorderable(var) {
+            let target = self.declarators_target(var, self.location.0);
+            self.reorder(target, &mut var.decls);
+        }
+    }
+
+    fn reorder_cases(&mut self, switch: &mut SwitchStmt) {
+        if let Some(before) = switches::constraints(switch) {
+            let target = self.switch_target(switch, before, self.location.0);
+            self.reorder(target, &mut switch.cases);
+        }
+    }
+
+    fn reorder_elements(&mut self, arr: &mut ArrayLit) {
+        if !self.eligible.array(arr) {
+            return;
+        }
+        if let Some(before) = arrays::constraints(arr) {
+            let target = self.array_target(arr, before, self.location.0);
+            self.reorder(target, &mut arr.elems);
+        }
+    }
+
+    // Only the innermost target right after a keep-order pragma is kept.
+    fn keep_order(&self, target: &Target) -> bool {
+        self.eligible.keep_order(target.kind.reach(), target.span.0)
+    }
+
+    fn finish(&mut self, state: LiteralState) {
+        self.checkpoint.update(state);
+        self.save_checkpoint(false);
+    }
+
+    // Writes the checkpoint if the interval's up since it was last written,
+    // or regardless with `now`, e.g. once the file's done.
+    fn save_checkpoint(&mut self, now: bool) {
+        if let Some(path) = &self.options.checkpoint {
+            if now || self.last_checkpoint.elapsed() >= self.options.checkpoint_interval {
+                self.checkpoint.save(path);
+                self.last_checkpoint = Instant::now();
             }
-            self.simhashes = newSimhashes;
         }
-        // println!("Made {} changes", changes);
-        // lit.props.shuffle(&mut thread_rng());
+    }
+}
+
+// Collects what `Compressor` would search, walking the same way so paths
+// line up: targets too big to solve exactly and not already settled by the
+// checkpoint or cache.
+// Outer literals get measured with their nested literals in source order,
+// since those haven't been reordered yet at this point.
+struct Planner<'c, 'a> {
+    compressor: &'c Compressor<'a>,
+    targets: Vec<Target>,
+    location: (usize, usize),
+}
+
+impl<'c, 'a> Visit for Planner<'c, 'a> {
+    fn visit_module_items(&mut self, items: &[ModuleItem]) {
+        for (i, item) in items.iter().enumerate() {
+            self.location = (i, 0);
+            item.visit_with(self);
+        }
+        self.plan_functions(items, None, functions::item_decl);
+        self.plan_statements(items, None, statements::item_stmt);
+        self.plan_imports(items);
+    }
+
+    fn visit_stmts(&mut self, stmts: &[Stmt]) {
+        stmts.visit_children_with(self);
+        self.plan_functions(stmts, Some(self.location.0), functions::stmt_decl);
+        self.plan_statements(stmts, Some(self.location.0), statements::stmt);
+    }
+
+    fn visit_class(&mut self, class: &Class) {
+        class.visit_children_with(self);
+        if class.body.len() > self.compressor.options.exact_threshold {
+            if let Some(before) = members::constraints(&class.body) {
+                let path = class_path(self.location.0, &members::keys(&class.body, self.compressor.cm));
+                self.plan(self.compressor.class_target(class, before, path));
+            }
+        }
+    }
+
+    fn visit_array_lit(&mut self, arr: &ArrayLit) {
+        arr.visit_children_with(self);
+        let compressor = self.compressor;
+        if arr.elems.len() > compressor.options.exact_threshold && compressor.eligible.array(arr) {
+            if let Some(before) = arrays::constraints(arr) {
+                self.plan(compressor.array_target(arr, before, self.location.0));
+            }
+        }
+    }
+
+    fn visit_switch_stmt(&mut self, switch: &SwitchStmt) {
+        switch.visit_children_with(self);
+        if switch.cases.len() > self.compressor.options.exact_threshold {
+            if let Some(before) = switches::constraints(switch) {
+                self.plan(self.compressor.switch_target(switch, before, self.location.0));
+            }
+        }
+    }
+
+    fn visit_var_decl(&mut self, var: &VarDecl) {
+        var.visit_children_with(self);
+        let compressor = self.compressor;
+        if compressor.reorder_declarators && var.decls.len() > compressor.options.exact_threshold && declarators::reorderable(var) {
+            self.plan(compressor.declarators_target(var, self.location.0));
+        }
+    }
+
+    fn visit_object_pat(&mut self, pat: &ObjectPat) {
+        pat.visit_children_with(self);
+        let options = &self.compressor.options;
+        if options.trust_getters && pat.props.len() > options.exact_threshold && patterns::reorderable(pat) {
+            self.plan(self.compressor.pattern_target(pat, self.location.0));
+        }
+    }
+
+    fn visit_import_decl(&mut self, decl: &ImportDecl) {
+        decl.visit_children_with(self);
+        if specifiers::imported(decl).len() > self.compressor.options.exact_threshold {
+            self.plan(self.compressor.import_target(decl, self.location.0));
+        }
+    }
+
+    fn visit_named_export(&mut self, export: &NamedExport) {
+        export.visit_children_with(self);
+        if specifiers::exported(export).len() > self.compressor.options.exact_threshold {
+            self.plan(self.compressor.export_target(export, self.location.0));
+        }
+    }
+
+    fn visit_object_lit(&mut self, lit: &ObjectLit) {
+        let path = format!("{}/{}", self.location.0, self.location.1);
+        self.location.1 += 1;
+
+        lit.visit_children_with(self);
+        if self.compressor.eligible.literal(lit) && lit.props.len() > self.compressor.options.exact_threshold {
+            self.plan(self.compressor.object_target(lit, path));
+        }
+    }
+}
+
+impl<'c, 'a> Planner<'c, 'a> {
+    fn plan_functions<T: swc_ecma_codegen::Node + Spanned>(&mut self, items: &[T], item: Option<usize>, decl: impl Fn(&T) -> Option<&FnDecl>) {
+        for run in functions::runs(items, &decl) {
+            if run.len() > self.compressor.options.exact_threshold {
+                let names = functions::names(&items[run.clone()], &decl);
+                let path = function_path(item, &names);
+                self.plan(self.compressor.function_target(&items[run], names, path));
+            }
+        }
+    }
+
+    fn plan_statements<T: swc_ecma_codegen::Node + Spanned>(&mut self, items: &[T], item: Option<usize>, stmt: impl Fn(&T) -> Option<&Stmt>) {
+        let compressor = self.compressor;
+        if !compressor.reorder_statements || items.len() <= compressor.options.exact_threshold {
+            return;
+        }
+        if let Some(before) = statements::constraints(items, &stmt) {
+            let keys = statements::keys(items, &stmt, compressor.cm);
+            let path = statements_path(item, &keys);
+            self.plan(compressor.statements_target(items, before, keys, path));
+        }
+    }
+
+    fn plan_imports(&mut self, items: &[ModuleItem]) {
+        let compressor = self.compressor;
+        let Some(side_effect_free) = &compressor.side_effect_free else {
+            return;
+        };
+        for run in imports::runs(items) {
+            if run.len() > compressor.options.exact_threshold {
+                if let Some(before) = imports::constraints(&items[run.clone()], side_effect_free) {
+                    self.plan(compressor.imports_target(&items[run], before));
+                }
+            }
+        }
+    }
+
+    fn plan(&mut self, target: Target) {
+        let compressor = self.compressor;
+        if compressor.keep_order(&target)
+            || compressor.exact(&target)
+            || compressor.checkpoint.get(target.kind.name(), target.span).is_some_and(|state| state.finished)
+            || compressor.cached(compressor.cache_key(&target), &target.problem).is_some()
+        {
+            return;
+        }
+        self.targets.push(target);
+    }
+}
+
+fn literal_state(kind: TargetKind, span: (u32, u32), search: &portfolio::Search, spent: (Duration, u64), finished: bool) -> LiteralState {
+    let best = search.best();
+    LiteralState {
+        kind: kind.name().to_string(),
+        span,
+        order: best.order,
+        size: best.size,
+        found_by: best.found_by.to_string(),
+        evaluations: spent.1 + search.budget.evaluations(),
+        elapsed_secs: (spent.0 + search.budget.elapsed()).as_secs_f64(),
+        finished,
+        strategies: if finished { vec![] } else { search.states() },
+    }
+}
+
+fn apply_order<T: Clone>(items: &mut [T], order: &[usize]) {
+    let reordered: Vec<T> = order.iter().map(|i| items[*i].clone()).collect();
+    items.clone_from_slice(&reordered);
+}
+
+impl<'a> VisitMut for Compressor<'a> {
+    // Function runs, statement lists and class bodies go after everything
+    // inside them, like nested literals before their parents.
+    fn visit_mut_module_items(&mut self, items: &mut Vec<ModuleItem>) {
+        for (i, item) in items.iter_mut().enumerate() {
+            self.location = (i, 0);
+            item.visit_mut_with(self);
+        }
+        self.reorder_functions(items, None, functions::item_decl);
+        self.reorder_statements(items, None, statements::item_stmt);
+        self.reorder_import_runs(items);
+    }
+
+    fn visit_mut_stmts(&mut self, stmts: &mut Vec<Stmt>) {
+        stmts.visit_mut_children_with(self);
+        self.reorder_functions(stmts, Some(self.location.0), functions::stmt_decl);
+        self.reorder_statements(stmts, Some(self.location.0), statements::stmt);
+    }
+
+    fn visit_mut_class(&mut self, class: &mut Class) {
+        class.visit_mut_children_with(self);
+        self.reorder_members(class);
+    }
+
+    fn visit_mut_array_lit(&mut self, arr: &mut ArrayLit) {
+        arr.visit_mut_children_with(self);
+        self.reorder_elements(arr);
+    }
+
+    fn visit_mut_switch_stmt(&mut self, switch: &mut SwitchStmt) {
+        switch.visit_mut_children_with(self);
+        self.reorder_cases(switch);
+    }
+
+    fn visit_mut_var_decl(&mut self, var: &mut VarDecl) {
+        var.visit_mut_children_with(self);
+        self.reorder_declarators(var);
+    }
+
+    fn visit_mut_object_pat(&mut self, pat: &mut ObjectPat) {
+        pat.visit_mut_children_with(self);
+        self.reorder_pattern(pat);
+    }
+
+    fn visit_mut_import_decl(&mut self, decl: &mut ImportDecl) {
+        decl.visit_mut_children_with(self);
+        self.reorder_imports(decl);
+    }
+
+    fn visit_mut_named_export(&mut self, export: &mut NamedExport) {
+        export.visit_mut_children_with(self);
+        self.reorder_exports(export);
+    }
+
+    // Innermost first, so by the time a literal is optimized its props
+    // serialize with their nested literals already in their final order.
+    // Paths are numbered on the way in, outer before inner.
+    fn visit_mut_object_lit(&mut self, lit: &mut ObjectLit) {
+        let path = format!("{}/{}", self.location.0, self.location.1);
+        self.location.1 += 1;
+
+        self.depth += 1;
+        lit.visit_mut_children_with(self);
+        if self.eligible.literal(lit) {
+            let target = self.object_target(lit, path);
+            self.reorder(target, &mut lit.props);
+        }
+        self.depth -= 1;
     }
 }
 
 fn ast_bytes(node: &impl swc_ecma_codegen::Node, cm: &Lrc<SourceMap>) -> Vec<u8> {
+    emit(node, cm, true, None, None)
+}
+
+// A statement as it appears among others, trailing semicolon included.
+fn statement_bytes(node: &impl swc_ecma_codegen::Node, cm: &Lrc<SourceMap>) -> Vec<u8> {
+    emit(node, cm, false, None, None)
+}
+
+// The whole file as it gets written out, with the comments --comments keeps.
+// Everything else is measured without comments, which don't move with the
+// parts being reordered anyway. `mappings` collects where each node's
+// original span ends up, for a source map.
+fn output_bytes(
+    module: &Module,
+    cm: &Lrc<SourceMap>,
+    comments: &SingleThreadedComments,
+    policy: options::CommentPolicy,
+    mappings: Option<&mut Vec<(BytePos, LineCol)>>,
+) -> Vec<u8> {
+    emit(module, cm, true, Some(&comments::kept(comments, policy)), mappings)
+}
+
+fn emit(
+    node: &impl swc_ecma_codegen::Node,
+    cm: &Lrc<SourceMap>,
+    omit_trailing_semi: bool,
+    comments: Option<&dyn Comments>,
+    mappings: Option<&mut Vec<(BytePos, LineCol)>>,
+) -> Vec<u8> {
     let mut buf = vec![];
     {
         let mut wr = Box::new(swc_ecma_codegen::text_writer::JsWriter::with_target(
             cm.clone(),
             "\n",
             &mut buf,
-            None,
+            mappings,
             swc_ecma_ast::EsVersion::Es2022,
         )) as Box<dyn swc_ecma_codegen::text_writer::WriteJs>;
 
-        wr = Box::new(swc_ecma_codegen::text_writer::omit_trailing_semi(wr));
+        if omit_trailing_semi {
+            wr = Box::new(swc_ecma_codegen::text_writer::omit_trailing_semi(wr));
+        }
 
         let mut emitter = swc_ecma_codegen::Emitter {
             cfg: swc_ecma_codegen::Config { minify: true },
-            comments: None,
+            comments,
             cm: cm.clone(),
             wr,
         };
@@ -159,36 +1103,15 @@ fn ast_bytes(node: &impl swc_ecma_codegen::Node, cm: &Lrc<SourceMap>) -> Vec<u8>
     buf
 }
 
-fn ast_bytes_compressed(node: &Stmt, cm: &Lrc<SourceMap>, quality: u32) -> usize {
-    let before_fold = std::time::SystemTime::now();
-    let buf = ast_bytes(node, cm);
-    let after_fold = std::time::SystemTime::now();
-
-    let len = buf.len();
-    let mut input = brotli::CompressorReader::new(buf.as_slice(), len, quality, len as u32);
-    let mut compressed_bytes = vec![];
-
-    let val = match input.read_to_end(&mut compressed_bytes) {
-        Ok(val) => val,
-        Err(e) => panic!("got error compressing {}", e)
-    };
-    let after_compression = std::time::SystemTime::now();
-    /*
-    println!("serialization to AST took {} millis, compression {} millis",
-        after_fold.duration_since(before_fold).unwrap().as_millis(),    
-        after_compression.duration_since(after_fold).unwrap().as_millis(),
-    );
-    */
-    val
+fn ast_bytes_compressed(node: &impl swc_ecma_codegen::Node, cm: &Lrc<SourceMap>, quality: u32) -> usize {
+    problem::compressed_len(&ast_bytes(node, cm), quality)
 }
 
-fn main() {
-
-    let cm: Lrc<SourceMap> = Default::default();
-
+fn parse(cm: &Lrc<SourceMap>, path: &Path) -> (Lrc<SourceFile>, Module, SingleThreadedComments) {
     let fm = cm
-            .load_file(std::path::Path::new("main.js"))
+            .load_file(path)
             .expect("failed to load .js");
+    let comments = SingleThreadedComments::default();
     
         let lexer = Lexer::new(
         // We want to parse ecmascript
@@ -196,7 +1119,7 @@ fn main() {
         // EsVersion defaults to es5
         Default::default(),
         StringInput::from(&*fm),
-        None,
+        Some(&comments),
     );
 
     let mut parser = Parser::new_from(lexer);
@@ -213,91 +1136,194 @@ fn main() {
         })
         .expect("failed to parser module");
 
-    let mut stmt = None;
-    for item in module.body {
-        if item.is_module_decl() {
-            println!("Got worthless decl!");
-            continue;
-        }
-        stmt = Some(item.expect_stmt());
-        break;
-    }
-    let initial_size = ast_bytes_compressed(&stmt.unwrap(), &cm, 9) as u32;
-    println!("Initial size is {}", initial_size);
-
-
-    // let before_bytes_compressed = ast_bytes_compressed(&statement_to_modify, &cm, 9);
-    // let mut sizes = vec![];
-    let min_size = std::sync::Arc::new(std::sync::atomic::AtomicU32::new(initial_size));
-    let total_processed = std::sync::Arc::new(std::sync::atomic::AtomicU32::new(0));
-    let mut handles = vec![];
-    for i in 0..1 {
-        let minsize_clone = std::sync::Arc::clone(&min_size);
-        let total_processed_clone = std::sync::Arc::clone(&total_processed);
-        handles.push(std::thread::spawn(move|| {
-            let cm: Lrc<SourceMap> = Default::default();
-
-            let fm = cm
-                 .load_file(std::path::Path::new("main.js"))
-                 .expect("failed to load .js");
-            
-                let lexer = Lexer::new(
-                // We want to parse ecmascript
-                Syntax::Es(Default::default()),
-                // EsVersion defaults to es5
-                Default::default(),
-                StringInput::from(&*fm),
-                None,
-            );
-        
-            let mut parser = Parser::new_from(lexer);
-        
-            for e in parser.take_errors() {
-                println!("Got parser error {:?}", e);
-            }
-        
-            let module = parser
-                .parse_module()
-                .map_err(|e| {
-                    // Unrecoverable fatal error occurred
-                    println!("Got parser error {:?}", e);
-                })
-                .expect("failed to parser module");
-        
-            let mut statement_to_modify = None;
-            for item in module.body {
-                if item.is_module_decl() {
-                    println!("Got worthless decl!");
-                    continue;
-                }
-                statement_to_modify = Some(item.expect_stmt());
-                break;
-            }
-        
-            let mut statement_to_modify = statement_to_modify.unwrap();
-
-            let mut threadlocal_compress = Compressor{
-                simhashMap: HashMap::new(), reverseSimhashes: HashMap::new(), simhashes: vec![], cm: &cm, runsWithNoChanges: 0, distanceMatrix: vec![]
-            };
-
-            for _ in 0..100000 {
-                // randomly mutate
-                swc_ecma_visit::visit_mut_stmt(&mut threadlocal_compress, &mut statement_to_modify);
-                let size = ast_bytes_compressed(&statement_to_modify, &cm, 9) as u32;
-                let prev_val = minsize_clone.fetch_min(size, std::sync::atomic::Ordering::AcqRel);
-                println!("Compressed size is {}", size);
-                if size < prev_val {
-                    println!("Got new size smaller than min on thread {}!!! {}", i, size);
-                }
-                if total_processed_clone.fetch_add(1, std::sync::atomic::Ordering::AcqRel) % 1000 == 0 {
-                    println!("Have now processed {} permutations", total_processed_clone.load(std::sync::atomic::Ordering::Acquire));
-                }
-            }   
-        }))
+    (fm, module, comments)
+}
+
+// A module from source text, for tests.
+#[cfg(test)]
+fn parse_source(source: &str) -> Module {
+    parse_source_with_comments(source).1
+}
+
+// Also the source map and comments, for tests that emit or read pragmas.
+#[cfg(test)]
+fn parse_source_with_comments(source: &str) -> (Lrc<SourceMap>, Module, SingleThreadedComments) {
+    let cm: Lrc<SourceMap> = Default::default();
+    let fm = cm.new_source_file(swc_common::FileName::Anon, source.to_string());
+    let comments = SingleThreadedComments::default();
+    let lexer = Lexer::new(Syntax::Es(Default::default()), Default::default(), StringInput::from(&*fm), Some(&comments));
+    let module = Parser::new_from(lexer).parse_module().expect("failed to parse test source");
+    (cm, module, comments)
+}
+
+// Writes the map next to the output as <output>.map and points the output at
+// it. Reordered nodes keep their original spans, so they map back to where
+// they were.
+fn write_source_map(cm: &Lrc<SourceMap>, mappings: &mut Vec<(BytePos, LineCol)>, output_path: &Path, output: &mut Vec<u8>) {
+    let mut map_path = output_path.as_os_str().to_owned();
+    map_path.push(".map");
+    let map_path = PathBuf::from(map_path);
+    let file = std::fs::File::create(&map_path).expect("failed to create source map");
+    // only now that it exists does the map's directory canonicalize
+    let map_dir = map_path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let mut map = cm.build_source_map_with_config(mappings, None, MapSources { map_dir: map_dir.canonicalize().ok() });
+    map.set_file(output_path.file_name().map(|name| name.to_string_lossy()).as_deref());
+    map.to_writer(file).expect("failed to write source map");
+    let name = map_path.file_name().unwrap().to_string_lossy();
+    output.extend_from_slice(format!("\n//# sourceMappingURL={}\n", name).as_bytes());
+    println!("Wrote source map {}", map_path.display());
+}
+
+// Names sources relative to the map, which is how consumers resolve them.
+struct MapSources {
+    map_dir: Option<PathBuf>,
+}
+
+impl SourceMapGenConfig for MapSources {
+    fn file_name_to_source(&self, name: &FileName) -> String {
+        let FileName::Real(path) = name else {
+            return name.to_string();
+        };
+        match (&self.map_dir, path.canonicalize()) {
+            (Some(map_dir), Ok(path)) => relative_path(map_dir, &path).to_string_lossy().into_owned(),
+            _ => path.to_string_lossy().into_owned(),
+        }
+    }
+}
+
+// `to` as seen from the directory `from`, both absolute.
+fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from: Vec<_> = from.components().collect();
+    let to: Vec<_> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut path: PathBuf = from[common..].iter().map(|_| "..").collect();
+    path.extend(&to[common..]);
+    path
+}
+
+fn main() {
+    let options = Options::from_args();
+    interrupt::install();
+
+    match options.mode {
+        Mode::Batch => batch::run(&options),
+        Mode::Search | Mode::Apply => {
+            optimize_file(&options, &options.input, &options.output);
+        }
+    }
+}
+
+/// Optimizes (or, in apply mode, reorders) one file and writes the result.
+/// Returns the compressed size before and after.
+fn optimize_file(options: &Options, input: &Path, output_path: &Path) -> (usize, usize) {
+    let cm: Lrc<SourceMap> = Default::default();
+    let (fm, mut module, comments) = parse(&cm, input);
+
+    let initial_size = problem::compressed_len(&output_bytes(&module, &cm, &comments, options.comments, None), options.quality);
+    println!("Initial size of {} is {}", input.display(), initial_size);
+
+    let input_hash = checkpoint::content_hash(fm.src.as_bytes());
+    let checkpoint = match &options.checkpoint {
+        Some(path) if options.resume => Checkpoint::load(path, input_hash),
+        _ => Checkpoint::new(input_hash, options.seed.unwrap_or_else(rand::random)),
+    };
+    println!("Using seed {}", checkpoint.seed);
+
+    let applying = match options.mode {
+        Mode::Apply => Some(Manifest::load(options.manifest.as_ref().unwrap())),
+        Mode::Search | Mode::Batch => None,
+    };
+    let dynamic_scope = statements::dynamic_scope(&module);
+    if dynamic_scope {
+        println!("{} uses eval or with, leaving statement and declarator order alone", input.display());
+    }
+    let reorder_statements = options.statements && !dynamic_scope;
+    let eligible = eligible::collect(&module, &comments, options.max_depth, &options.unordered);
+    let side_effect_free = Some(&options.side_effect_free).filter(|patterns| !patterns.is_empty()).map(|patterns| batch::glob_set(patterns));
+    let mut compressor = Compressor {
+        cm: &cm,
+        options: options.clone(),
+        stats: Stats::default(),
+        checkpoint,
+        last_checkpoint: Instant::now(),
+        manifest: Manifest::default(),
+        applying,
+        warm_start: options.warm_start.as_deref().map(|path| warm_start::load(path, options)),
+        cache: options.cache.clone().map(Cache::new),
+        location: (0, 0),
+        depth: 0,
+        time_share: None,
+        allocated: vec![],
+        reorder_statements,
+        reorder_declarators: !dynamic_scope,
+        side_effect_free,
+        eligible: &eligible,
+    };
+    if options.allocate && options.mode != Mode::Apply {
+        compressor.allocate(&module, options.file_budget.unwrap_or(options.limits.time));
+    } else if let (Some(budget), true) = (options.file_budget, options.mode != Mode::Apply) {
+        // split between exactly the targets the visit will end up searching
+        let bytes = compressor
+            .searchable(&module)
+            .iter()
+            .map(|target| target.problem.render(&(0..target.problem.units.len()).collect::<Vec<_>>()).len())
+            .sum();
+        compressor.time_share = Some((budget, bytes));
+    }
+    swc_ecma_visit::visit_mut_module(&mut compressor, &mut module);
+    compressor.save_checkpoint(true);
+    if let (Mode::Search | Mode::Batch, Some(budget)) = (options.mode, options.joint) {
+        joint::run(&mut module, &cm, &eligible, options, budget, compressor.checkpoint.seed, &mut compressor.manifest);
     }
 
-    for i in handles {
-        i.join().unwrap();
+    if let (Mode::Search, Some(path)) = (options.mode, &options.manifest) {
+        compressor.manifest.save(path);
+        println!("Wrote manifest {}", path.display());
     }
-    // sizes.sort_unstable();
-}
\ No newline at end of file
+
+    let mut mappings = vec![];
+    let mut output = output_bytes(&module, &cm, &comments, options.comments, options.source_map.then_some(&mut mappings));
+    let final_size = problem::compressed_len(&output, options.quality);
+    if options.source_map {
+        write_source_map(&cm, &mut mappings, output_path, &mut output);
+    }
+    std::fs::write(output_path, &output).expect("failed to write output");
+    let stats = &compressor.stats;
+    println!(
+        "Optimized {} literals, {} function runs, {} statement lists, {} class bodies, {} specifier lists, {} import runs, {} patterns, {} declarations, {} switches and {} arrays ({} skipped, {} more from cache), saving {} bytes across them{}",
+        stats.literals,
+        stats.function_runs,
+        stats.statement_lists,
+        stats.class_bodies,
+        stats.specifier_lists,
+        stats.import_runs,
+        stats.patterns,
+        stats.declarations,
+        stats.switches,
+        stats.arrays,
+        stats.skipped,
+        stats.cached,
+        stats.bytes_saved,
+        if interrupt::interrupted() { " before being interrupted" } else { "" },
+    );
+    if stats.levels.len() > 1 {
+        for (depth, (literals, saved)) in stats.levels.iter().enumerate() {
+            println!("  depth {}: {} literals, saving {} bytes", depth + 1, literals, saved);
+        }
+    }
+    println!("Final size is {} ({} bytes saved), wrote {}", final_size, initial_size as isize - final_size as isize, output_path.display());
+    (initial_size, final_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn relative_paths_go_up_to_the_common_directory() {
+        let relative = |from: &str, to: &str| super::relative_path(Path::new(from), Path::new(to));
+        assert_eq!(relative("/a/b", "/a/b/c.js"), PathBuf::from("c.js"));
+        assert_eq!(relative("/a/out/nested", "/a/src/c.js"), PathBuf::from("../../src/c.js"));
+        assert_eq!(relative("/", "/a/c.js"), PathBuf::from("a/c.js"));
+    }
+}
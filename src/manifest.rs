@@ -0,0 +1,153 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use swc_common::sync::Lrc;
+use swc_common::SourceMap;
+use swc_ecma_ast::PropOrSpread;
+
+/// The order picked for every optimized literal, so a later build can apply
+/// it without searching again.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub literals: Vec<Entry>,
+}
+
+/// One reordered target. `keys` label its parts in source order and `order`
+/// indexes into them. The keys are what actually pin an entry to a target,
+/// the path only tells apart targets that share keys. Paths start with the
+/// index of the module item the target is in, then say what it is:
+/// "<item>/<n>" for the nth object literal in the item, and
+/// "<item>/<kind>:<first key>" for the rest, where kind is fn (a run of
+/// function declarations), stmts, class (members), pattern, var
+/// (declarators), switch (cases) or array (elements). A run of import
+/// declarations is "imports:<first key>", named import and export
+/// specifiers are "<item>/import" and "<item>/export", and top-level runs
+/// of functions and statements leave out the item, as "fn:<first name>" and
+/// "stmts".
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub path: String,
+    pub keys: Vec<String>,
+    pub order: Vec<usize>,
+    pub size: usize,
+    // where the target is in this run's input, which unlike the path still
+    // finds it once module items have moved; not saved
+    #[serde(skip)]
+    pub span: (u32, u32),
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> Manifest {
+        let json = std::fs::read_to_string(path).expect("failed to read manifest");
+        serde_json::from_str(&json).expect("failed to parse manifest")
+    }
+
+    pub fn save(&self, path: &Path) {
+        std::fs::write(path, serde_json::to_string_pretty(self).unwrap()).expect("failed to write manifest");
+    }
+
+    /// The entry for a literal at `path` with `keys`: one at the same path
+    /// with the same keys if there is one, otherwise the first with the same
+    /// keys anywhere.
+    pub fn find(&self, path: &str, keys: &[String]) -> Option<&Entry> {
+        let same_keys = |entry: &&Entry| same_key_set(&entry.keys, keys);
+        self.literals
+            .iter()
+            .filter(same_keys)
+            .find(|entry| entry.path == path)
+            .or_else(|| self.literals.iter().find(same_keys))
+    }
+}
+
+impl Entry {
+    /// This entry's order for a literal whose props are `keys`, which may
+    /// list the same keys in a different source order. None if the key sets
+    /// differ or the order isn't a permutation of the keys.
+    pub fn order_for(&self, keys: &[String]) -> Option<Vec<usize>> {
+        if !same_key_set(&self.keys, keys) || !self.valid_order() {
+            return None;
+        }
+        let mut used = vec![false; keys.len()];
+        self.order
+            .iter()
+            .map(|i| {
+                // duplicate keys map onto their occurrences in turn
+                let position = (0..keys.len()).find(|j| !used[*j] && keys[*j] == self.keys[*i])?;
+                used[position] = true;
+                Some(position)
+            })
+            .collect()
+    }
+
+    /// Whether the order is a permutation of the entry's own keys, which a
+    /// hand-edited manifest needn't be and shouldn't turn into a panic.
+    pub fn valid_order(&self) -> bool {
+        let mut sorted = self.order.clone();
+        sorted.sort_unstable();
+        sorted.iter().copied().eq(0..self.keys.len())
+    }
+}
+
+fn same_key_set(a: &[String], b: &[String]) -> bool {
+    let mut a = a.to_vec();
+    let mut b = b.to_vec();
+    a.sort();
+    b.sort();
+    a == b
+}
+
+/// A label per prop: its key where it has a plain one, otherwise (spreads,
+/// computed keys) its minified source.
+pub fn prop_keys(props: &[PropOrSpread], cm: &Lrc<SourceMap>) -> Vec<String> {
+    props
+        .iter()
+        .map(|prop| match crate::baselines::prop_key(prop) {
+            Some(key) => key,
+            None => String::from_utf8_lossy(&crate::ast_bytes(prop, cm)).into_owned(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Entry;
+
+    fn keys(keys: &[&str]) -> Vec<String> {
+        keys.iter().map(|key| key.to_string()).collect()
+    }
+
+    fn entry(entry_keys: &[&str], order: Vec<usize>) -> Entry {
+        Entry { path: "0/0".to_string(), keys: keys(entry_keys), order, size: 0, span: (0, 0) }
+    }
+
+    #[test]
+    fn order_for_maps_onto_a_new_source_order() {
+        let entry = entry(&["a", "b", "c"], vec![2, 0, 1]);
+        assert_eq!(entry.order_for(&keys(&["a", "b", "c"])), Some(vec![2, 0, 1]));
+        // c, a, b wherever they are now
+        assert_eq!(entry.order_for(&keys(&["c", "b", "a"])), Some(vec![0, 2, 1]));
+    }
+
+    #[test]
+    fn order_for_takes_duplicate_keys_in_turn() {
+        // b then both a's, which go to the a's in the order they come up
+        let entry = entry(&["a", "b", "a"], vec![1, 2, 0]);
+        assert_eq!(entry.order_for(&keys(&["b", "a", "a"])), Some(vec![0, 1, 2]));
+        assert_eq!(entry.order_for(&keys(&["a", "a", "b"])), Some(vec![2, 0, 1]));
+    }
+
+    #[test]
+    fn order_for_refuses_other_keys() {
+        let entry = entry(&["a", "b"], vec![1, 0]);
+        assert_eq!(entry.order_for(&keys(&["a", "c"])), None);
+        assert_eq!(entry.order_for(&keys(&["a", "b", "b"])), None);
+    }
+
+    #[test]
+    fn order_for_refuses_orders_that_arent_permutations() {
+        let keys = keys(&["a", "b", "c"]);
+        assert_eq!(entry(&["a", "b", "c"], vec![0, 0, 1]).order_for(&keys), None);
+        assert_eq!(entry(&["a", "b", "c"], vec![0, 1]).order_for(&keys), None);
+        assert_eq!(entry(&["a", "b", "c"], vec![0, 1, 3]).order_for(&keys), None);
+    }
+}
@@ -0,0 +1,108 @@
+use swc_common::sync::Lrc;
+use swc_common::SourceMap;
+use swc_ecma_ast::{ClassMember, MethodKind};
+
+use crate::baselines::prop_name;
+use crate::problem;
+
+/// What has to stay ahead of what in a class body, the way `Problem::before`
+/// takes it. Methods, getters, setters and the constructor only define
+/// properties, so like an object literal's props their order shows through
+/// reflection alone. Fields and static blocks run code and keep their place,
+/// as do members with decorators or computed keys, which get evaluated in
+/// order when the class is defined. Two members for the same property stay
+/// in order since the later one wins, except for a getter and setter pair.
+/// None if nothing can move.
+pub fn constraints(members: &[ClassMember]) -> Option<Vec<Vec<usize>>> {
+    let labels: Vec<Option<Label>> = members.iter().map(label).collect();
+    let before = problem::constraints(labels.len(), |j, i| ordered(&labels[j], &labels[i]));
+    if problem::forced(&before) {
+        return None;
+    }
+    Some(before)
+}
+
+// The property a movable member defines: whether it's static, its name
+// ("#name" if private) and what kind of method it is.
+struct Label {
+    is_static: bool,
+    name: String,
+    kind: MethodKind,
+}
+
+fn label(member: &ClassMember) -> Option<Label> {
+    match member {
+        ClassMember::Method(method) if method.function.decorators.is_empty() => Some(Label {
+            is_static: method.is_static,
+            name: prop_name(&method.key)?,
+            kind: method.kind,
+        }),
+        ClassMember::PrivateMethod(method) if method.function.decorators.is_empty() => Some(Label {
+            is_static: method.is_static,
+            name: format!("#{}", method.key.id.sym),
+            kind: method.kind,
+        }),
+        ClassMember::Constructor(_) => Some(Label { is_static: false, name: "constructor".to_string(), kind: MethodKind::Method }),
+        _ => None,
+    }
+}
+
+fn ordered(a: &Option<Label>, b: &Option<Label>) -> bool {
+    let (Some(a), Some(b)) = (a, b) else {
+        return true;
+    };
+    let accessor_pair = matches!((a.kind, b.kind), (MethodKind::Getter, MethodKind::Setter) | (MethodKind::Setter, MethodKind::Getter));
+    a.is_static == b.is_static && a.name == b.name && !accessor_pair
+}
+
+/// A label per member for manifests, like "static get name", or a hash of
+/// its minified source for members that don't move.
+pub fn keys(members: &[ClassMember], cm: &Lrc<SourceMap>) -> Vec<String> {
+    members
+        .iter()
+        .map(|member| match label(member) {
+            Some(label) => {
+                let kind = match label.kind {
+                    MethodKind::Method => "",
+                    MethodKind::Getter => "get ",
+                    MethodKind::Setter => "set ",
+                };
+                format!("{}{}{}", if label.is_static { "static " } else { "" }, kind, label.name)
+            }
+            None => format!("#{:016x}", crate::checkpoint::content_hash(&crate::ast_bytes(member, cm))),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use swc_ecma_ast::{ClassMember, Decl, ModuleItem, Stmt};
+
+    use super::constraints;
+
+    fn body(source: &str) -> Vec<ClassMember> {
+        match crate::parse_source(source).body.remove(0) {
+            ModuleItem::Stmt(Stmt::Decl(Decl::Class(class))) => class.class.body,
+            _ => panic!("not a class"),
+        }
+    }
+
+    #[test]
+    fn getter_and_setter_pairs_move_freely() {
+        let before = constraints(&body("class A { get x() {} set x(v) {} y() {} }")).unwrap();
+        assert_eq!(before, vec![Vec::<usize>::new(); 3]);
+    }
+
+    #[test]
+    fn later_definitions_of_a_property_stay_later() {
+        let before = constraints(&body("class A { get x() {} set x(v) {} x() {} static x() {} }")).unwrap();
+        assert_eq!(before, vec![vec![], vec![], vec![0, 1], vec![]]);
+    }
+
+    #[test]
+    fn fields_keep_their_place() {
+        let before = constraints(&body("class A { b() {} a = 1; c() {} d() {} }")).unwrap();
+        assert_eq!(before, vec![vec![], vec![0], vec![1], vec![1]]);
+        assert_eq!(constraints(&body("class A { a = 1; b = 2; }")), None);
+    }
+}
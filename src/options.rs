@@ -0,0 +1,218 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::budget::Limits;
+use crate::strategies::Strategy;
+
+/// Command line knobs. Everything has a default so running with no arguments
+/// still works on ./main.js.
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub mode: Mode,
+    pub input: PathBuf,
+    pub output: PathBuf,
+    // brotli quality used when measuring compressed size
+    pub quality: u32,
+    // object literals with at most this many props are solved by trying every order
+    pub exact_threshold: usize,
+    // strategies raced against each other on larger literals
+    pub strategies: Vec<Strategy>,
+    pub threads: usize,
+    // when to stop searching each larger literal; in batch mode `time` is
+    // for the whole batch instead, and with `allocate` for the whole file
+    pub limits: Limits,
+    // RNG seed for the workers, random if not given
+    pub seed: Option<u64>,
+    pub checkpoint: Option<PathBuf>,
+    pub checkpoint_interval: Duration,
+    // pick up from the checkpoint file instead of starting over
+    pub resume: bool,
+    // written after a search, read in apply mode
+    pub manifest: Option<PathBuf>,
+    // previous manifest or output file to seed the search with
+    pub warm_start: Option<PathBuf>,
+    // directory of orders found by earlier runs, keyed by literal content
+    pub cache: Option<PathBuf>,
+    // directory to optimize in batch mode
+    pub batch_dir: Option<PathBuf>,
+    // globs (relative to the batch directory) a file has to match / mustn't match
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    // mirror directory for batch outputs, otherwise they go next to the inputs
+    pub out_dir: Option<PathBuf>,
+    // files optimized at once in batch mode
+    pub jobs: usize,
+    // total time for all larger literals in a file, split between them by
+    // size; batch mode sets this per file, otherwise `limits.time` is per literal
+    pub file_budget: Option<Duration>,
+    // share the file's budget between its larger literals by how much each
+    // is saving, rather than giving every literal its own
+    pub allocate: bool,
+    // after optimizing literals one by one, time to spend reordering them
+    // again against the whole file's compressed size
+    pub joint: Option<Duration>,
+    // literals nested deeper than this (1 is outermost only) are left alone
+    pub max_depth: usize,
+    // also reorder declarations in statement lists, as far as their
+    // dependencies allow
+    pub statements: bool,
+    // globs of module specifiers that are free of side effects, like
+    // package.json's `sideEffects: false`; imports of those get reordered
+    pub side_effect_free: Vec<String>,
+    // assume reading a property never runs code, so destructuring patterns
+    // can be reordered
+    pub trust_getters: bool,
+    // variables and properties holding arrays (or `new Set`s of them) whose
+    // order doesn't matter, so their elements get reordered
+    pub unordered: Vec<String>,
+    // which of the input's comments make it into the output
+    pub comments: CommentPolicy,
+    // also write <output>.map
+    pub source_map: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    // search for good orders (and write a manifest if asked to)
+    Search,
+    // reorder according to an existing manifest, no searching
+    Apply,
+    // search every .js file in a directory
+    Batch,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            mode: Mode::Search,
+            input: PathBuf::from("main.js"),
+            output: PathBuf::from("main.out.js"),
+            quality: 9,
+            exact_threshold: 8,
+            strategies: Strategy::ALL.to_vec(),
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            limits: Limits {
+                time: Duration::from_secs(10),
+                evaluations: None,
+                patience: None,
+                min_bytes_per_minute: None,
+            },
+            seed: None,
+            checkpoint: None,
+            checkpoint_interval: Duration::from_secs(60),
+            resume: false,
+            manifest: None,
+            warm_start: None,
+            cache: None,
+            batch_dir: None,
+            include: vec![],
+            exclude: vec![],
+            out_dir: None,
+            jobs: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            file_budget: None,
+            allocate: false,
+            joint: None,
+            max_depth: usize::MAX,
+            statements: false,
+            side_effect_free: vec![],
+            trust_getters: false,
+            unordered: vec![],
+            comments: CommentPolicy::Legal,
+            source_map: false,
+        }
+    }
+}
+
+impl Options {
+    pub fn from_args() -> Options {
+        let mut options = Options::default();
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--input" => options.input = parse_value(&arg, args.next()),
+                "--output" => options.output = parse_value(&arg, args.next()),
+                "--quality" => options.quality = parse_value(&arg, args.next()),
+                "--exact-threshold" => options.exact_threshold = parse_value(&arg, args.next()),
+                "--strategies" => options.strategies = parse_strategies(&arg, args.next()),
+                "--threads" => options.threads = parse_value(&arg, args.next()),
+                "--budget" => options.limits.time = Duration::from_secs_f64(parse_value(&arg, args.next())),
+                "--max-evaluations" => options.limits.evaluations = Some(parse_value(&arg, args.next())),
+                "--patience" => options.limits.patience = Some(parse_value(&arg, args.next())),
+                "--min-rate" => options.limits.min_bytes_per_minute = Some(parse_value(&arg, args.next())),
+                "--seed" => options.seed = Some(parse_value(&arg, args.next())),
+                "--checkpoint" => options.checkpoint = Some(parse_value(&arg, args.next())),
+                "--checkpoint-interval" => options.checkpoint_interval = Duration::from_secs_f64(parse_value(&arg, args.next())),
+                "--resume" => options.resume = true,
+                "--manifest" => options.manifest = Some(parse_value(&arg, args.next())),
+                "--warm-start" => options.warm_start = Some(parse_value(&arg, args.next())),
+                "--cache" => options.cache = Some(parse_value(&arg, args.next())),
+                "--include" => options.include.push(parse_value(&arg, args.next())),
+                "--exclude" => options.exclude.push(parse_value(&arg, args.next())),
+                "--out-dir" => options.out_dir = Some(parse_value(&arg, args.next())),
+                "--jobs" => options.jobs = parse_value(&arg, args.next()),
+                "--allocate" => options.allocate = true,
+                "--statements" => options.statements = true,
+                "--trust-getters" => options.trust_getters = true,
+                "--source-map" => options.source_map = true,
+                "--comments" => options.comments = parse_value(&arg, args.next()),
+                "--unordered" => options.unordered.push(parse_value(&arg, args.next())),
+                "--side-effect-free" => options.side_effect_free.push(parse_value(&arg, args.next())),
+                "--max-depth" => options.max_depth = parse_value(&arg, args.next()),
+                "--joint" => options.joint = Some(Duration::from_secs_f64(parse_value(&arg, args.next()))),
+                "apply" => options.mode = Mode::Apply,
+                "batch" => {
+                    options.mode = Mode::Batch;
+                    options.batch_dir = Some(parse_value(&arg, args.next()));
+                }
+                _ => panic!("unknown argument {}", arg),
+            }
+        }
+        if options.resume && options.checkpoint.is_none() {
+            panic!("--resume needs --checkpoint to know what to resume from");
+        }
+        if options.mode == Mode::Apply && options.manifest.is_none() {
+            panic!("apply needs --manifest to know what to apply");
+        }
+        if options.mode == Mode::Batch && (options.checkpoint.is_some() || options.manifest.is_some() || options.warm_start.is_some()) {
+            panic!("--checkpoint, --manifest and --warm-start are per file and don't work in batch mode");
+        }
+        options
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CommentPolicy {
+    None,
+    // license headers and the like, see `comments::kept`
+    Legal,
+    All,
+}
+
+impl std::str::FromStr for CommentPolicy {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, ()> {
+        match name {
+            "none" => Ok(CommentPolicy::None),
+            "legal" => Ok(CommentPolicy::Legal),
+            "all" => Ok(CommentPolicy::All),
+            _ => Err(()),
+        }
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> T {
+    let value = value.unwrap_or_else(|| panic!("{} needs a value", flag));
+    match value.parse() {
+        Ok(parsed) => parsed,
+        Err(_) => panic!("invalid value {} for {}", value, flag),
+    }
+}
+
+fn parse_strategies(flag: &str, value: Option<String>) -> Vec<Strategy> {
+    let value = value.unwrap_or_else(|| panic!("{} needs a value", flag));
+    value
+        .split(',')
+        .map(|name| Strategy::from_name(name).unwrap_or_else(|| panic!("unknown strategy {}", name)))
+        .collect()
+}
@@ -0,0 +1,84 @@
+use std::collections::HashSet;
+
+use swc_ecma_ast::{ObjectPat, ObjectPatProp, Pat, PropName};
+
+use crate::baselines::prop_name;
+
+/// Whether a destructuring pattern's props can go in any order. They're read
+/// and assigned one after another, which only shows if reading a property
+/// runs code, i.e. a getter or proxy, so this is only used with
+/// --trust-getters. Defaults, computed keys and rest elements run code or
+/// depend on the other props, and so can assigning to anything but a plain
+/// name. Every name has to be bound once, otherwise the last one wins.
+pub fn reorderable(pat: &ObjectPat) -> bool {
+    if pat.props.len() < 2 {
+        return false;
+    }
+    let mut bound = HashSet::new();
+    pat.props.iter().all(|prop| {
+        let name = match prop {
+            ObjectPatProp::Assign(assign) if assign.value.is_none() => &assign.key.sym,
+            ObjectPatProp::KeyValue(kv) if !matches!(kv.key, PropName::Computed(_)) => match &*kv.value {
+                Pat::Ident(ident) => &ident.id.sym,
+                _ => return false,
+            },
+            _ => return false,
+        };
+        bound.insert(name.to_string())
+    })
+}
+
+/// A label per prop for manifests: the key it reads.
+pub fn keys(pat: &ObjectPat) -> Vec<String> {
+    pat.props
+        .iter()
+        .map(|prop| match prop {
+            ObjectPatProp::Assign(assign) => assign.key.sym.to_string(),
+            ObjectPatProp::KeyValue(kv) => prop_name(&kv.key).unwrap(),
+            ObjectPatProp::Rest(_) => unreachable!("patterns with rest elements aren't reordered"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use swc_ecma_ast::{Decl, ModuleItem, ObjectPat, Pat, Stmt};
+
+    // The pattern `const ... = o` declares in `source`.
+    fn pattern(source: &str) -> ObjectPat {
+        let ModuleItem::Stmt(Stmt::Decl(Decl::Var(var))) = crate::parse_source(source).body.remove(0) else {
+            panic!("expected a declaration in {}", source);
+        };
+        match var.decls.into_iter().next().unwrap().name {
+            Pat::Object(pat) => pat,
+            _ => panic!("expected an object pattern in {}", source),
+        }
+    }
+
+    fn reorderable(source: &str) -> bool {
+        super::reorderable(&pattern(source))
+    }
+
+    #[test]
+    fn plain_names_are_reorderable() {
+        assert!(reorderable("const {a, b: c, 'd': e} = o;"));
+        assert_eq!(super::keys(&pattern("const {b, a: c} = o;")), vec!["b", "a"]);
+    }
+
+    #[test]
+    fn props_that_run_code_or_depend_on_each_other_stay_put() {
+        // only one
+        assert!(!reorderable("const {a} = o;"));
+        // defaults run code
+        assert!(!reorderable("const {a = f(), b} = o;"));
+        assert!(!reorderable("const {a: c = 1, b} = o;"));
+        // computed keys too
+        assert!(!reorderable("const {[k]: a, b} = o;"));
+        // the rest depends on what came before
+        assert!(!reorderable("const {a, ...rest} = o;"));
+        // nested patterns read their own props
+        assert!(!reorderable("const {a: {c}, b} = o;"));
+        // the last one wins
+        assert!(!reorderable("const {a, b: a} = o;"));
+    }
+}
@@ -0,0 +1,150 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::budget::{Budget, Limits};
+use crate::problem::Problem;
+use crate::strategies::{State, Strategy};
+
+/// Everything the workers racing on one problem share: the problem itself,
+/// the smallest order found so far and the budget they're all drawing from,
+/// plus where each strategy has got to, for checkpoints.
+pub struct Search<'a> {
+    pub problem: &'a Problem,
+    pub budget: Budget,
+    best: Mutex<Best>,
+    // from a checkpoint, until a worker picks each up
+    saved: Mutex<Vec<(Strategy, State)>>,
+    // each slot's state as of its last slice
+    states: Mutex<Vec<Option<(Strategy, State)>>>,
+}
+
+#[derive(Clone)]
+pub struct Best {
+    pub order: Vec<usize>,
+    pub size: usize,
+    // strategy (or baseline) that produced `order`
+    pub found_by: &'static str,
+}
+
+impl<'a> Search<'a> {
+    pub fn new(problem: &'a Problem, seed: Best, limits: &Limits) -> Search<'a> {
+        Search {
+            problem,
+            budget: Budget::new(limits, seed.size),
+            best: Mutex::new(seed),
+            saved: Mutex::new(vec![]),
+            states: Mutex::new(vec![]),
+        }
+    }
+
+    /// Has the strategies carry on from `states`, as returned by `states`.
+    pub fn resuming(self, states: Vec<(Strategy, State)>) -> Search<'a> {
+        Search { saved: Mutex::new(states), ..self }
+    }
+
+    /// Where every strategy has got to, in a form a later search can resume.
+    pub fn states(&self) -> Vec<(Strategy, State)> {
+        let mut states: Vec<(Strategy, State)> = self.states.lock().unwrap().iter().flatten().cloned().collect();
+        states.extend(self.saved.lock().unwrap().iter().cloned());
+        states
+    }
+
+    // A saved state for `strategy`, if there's one left.
+    fn take_saved(&self, strategy: Strategy) -> Option<State> {
+        let mut saved = self.saved.lock().unwrap();
+        let position = saved.iter().position(|(saved, _)| *saved == strategy)?;
+        Some(saved.remove(position).1)
+    }
+
+    fn publish(&self, slot: usize, strategy: Strategy, state: State) {
+        let mut states = self.states.lock().unwrap();
+        if states.len() <= slot {
+            states.resize(slot + 1, None);
+        }
+        states[slot] = Some((strategy, state));
+    }
+
+    /// Compressed size of `order`, counted against the budget.
+    pub fn size(&self, order: &[usize]) -> usize {
+        let size = self.problem.size(order);
+        self.budget.record(size);
+        size
+    }
+
+    /// Records `order` if it beats the current best. Returns whether it did.
+    pub fn offer(&self, order: &[usize], size: usize, strategy: Strategy) -> bool {
+        let mut best = self.best.lock().unwrap();
+        if size >= best.size {
+            return false;
+        }
+        best.order = order.to_vec();
+        best.size = size;
+        best.found_by = strategy.name();
+        true
+    }
+
+    pub fn best(&self) -> Best {
+        self.best.lock().unwrap().clone()
+    }
+
+    pub fn done(&self) -> bool {
+        self.budget.exhausted()
+    }
+}
+
+// how often `run` hands control back to its caller while workers search
+const TICK: Duration = Duration::from_millis(100);
+// how long a worker runs one of its strategies before turning to the next
+const SLICE: Duration = Duration::from_millis(500);
+
+/// Races `strategies` against each other until the budget runs out, all
+/// starting from the search's current best. Every strategy gets a slot (or
+/// several, with more threads than strategies), dealt round-robin to
+/// `threads` workers that take turns between their slots a slice at a time,
+/// so the whole portfolio runs even on one thread. Slots pick up states the
+/// search is resuming, and slot `i` seeds its RNG with `rng_seed + i`.
+/// Meanwhile `tick` gets called on this thread every so
+/// often, e.g. to checkpoint.
+pub fn run(search: &Search, strategies: &[Strategy], threads: usize, rng_seed: u64, mut tick: impl FnMut(&Search)) {
+    if search.problem.units.len() < 2 || strategies.is_empty() {
+        return;
+    }
+
+    let workers = threads.max(1);
+    let slots = workers.max(strategies.len());
+    std::thread::scope(|scope| {
+        for worker in 0..workers {
+            scope.spawn(move || {
+                let mut slots: Vec<(usize, Strategy, StdRng, Option<State>)> = (worker..slots)
+                    .step_by(workers)
+                    .map(|slot| {
+                        let strategy = strategies[slot % strategies.len()];
+                        (slot, strategy, StdRng::seed_from_u64(rng_seed.wrapping_add(slot as u64)), search.take_saved(strategy))
+                    })
+                    .collect();
+                while !search.done() {
+                    for (slot, strategy, rng, state) in &mut slots {
+                        if search.done() {
+                            break;
+                        }
+                        let next = strategy.run(search, state.take(), rng, Instant::now() + SLICE);
+                        search.publish(*slot, *strategy, next.resumable(search));
+                        *state = Some(next);
+                    }
+                }
+            });
+        }
+        while search.budget.stop_reason().is_none() {
+            std::thread::sleep(TICK);
+            tick(search);
+        }
+    });
+    println!(
+        "Stopped after {} evaluations ({})",
+        search.budget.evaluations(),
+        search.budget.stop_reason().unwrap_or("done"),
+    );
+}
@@ -0,0 +1,29 @@
+use swc_common::comments::SingleThreadedComments;
+use swc_common::BytePos;
+
+/// What a `/* @js_moving ... */` comment says about the node right after it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pragma {
+    // `keep-order`: leave it exactly as written
+    KeepOrder,
+    // `unordered`: its order doesn't matter, even where it'd otherwise be
+    // left alone
+    Unordered,
+}
+
+/// The pragma in the comments leading up to `lo`, where a node starts. The
+/// last one wins if there are several.
+pub fn pragma(comments: &SingleThreadedComments, lo: u32) -> Option<Pragma> {
+    comments.with_leading(BytePos(lo), |comments| comments.iter().rev().find_map(|comment| parse(&comment.text)))
+}
+
+fn parse(text: &str) -> Option<Pragma> {
+    match text.trim().strip_prefix("@js_moving")?.trim() {
+        "keep-order" => Some(Pragma::KeepOrder),
+        "unordered" => Some(Pragma::Unordered),
+        other => {
+            println!("Ignoring unknown pragma @js_moving {}", other);
+            None
+        }
+    }
+}
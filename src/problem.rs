@@ -0,0 +1,158 @@
+use std::io::Read;
+
+/// A run of independently serialized units (e.g. the props of an object
+/// literal) whose order we're free to pick, plus the bytes around them.
+/// Rendering is just concatenation, so evaluating an order never has to go
+/// back through the emitter.
+pub struct Problem {
+    pub prefix: Vec<u8>,
+    pub units: Vec<Vec<u8>>,
+    pub separator: Vec<u8>,
+    pub suffix: Vec<u8>,
+    pub quality: u32,
+    // units that have to come before each unit, empty if any order goes
+    pub before: Vec<Vec<usize>>,
+}
+
+impl Problem {
+    /// The closest order to `order` that keeps every unit after the ones it
+    /// has to follow: units are taken in `order`, each as soon as it's
+    /// allowed. Strategies can then search freely, since every order they
+    /// try gets laid out (and measured) this way.
+    pub fn repair(&self, order: &[usize]) -> Vec<usize> {
+        if self.before.is_empty() {
+            return order.to_vec();
+        }
+        let mut placed = vec![false; order.len()];
+        let mut repaired = Vec::with_capacity(order.len());
+        while repaired.len() < order.len() {
+            let next = *order
+                .iter()
+                .find(|unit| !placed[**unit] && self.before[**unit].iter().all(|before| placed[*before]))
+                .unwrap();
+            placed[next] = true;
+            repaired.push(next);
+        }
+        repaired
+    }
+
+    /// How many units aren't pinned down by `before`, i.e. can end up
+    /// somewhere else relative to at least one other unit.
+    pub fn movable(&self) -> usize {
+        let n = self.units.len();
+        if self.before.is_empty() {
+            return n;
+        }
+        // everything each unit has to follow, directly or not, as bitsets
+        let words = n.div_ceil(64);
+        let mut ancestors = vec![vec![0u64; words]; n];
+        for unit in self.repair(&(0..n).collect::<Vec<_>>()) {
+            for &before in &self.before[unit] {
+                let mut inherited = ancestors[before].clone();
+                inherited[before / 64] |= 1 << (before % 64);
+                for (word, bits) in ancestors[unit].iter_mut().zip(inherited) {
+                    *word |= bits;
+                }
+            }
+        }
+        (0..n)
+            .filter(|i| {
+                let follows: usize = ancestors[*i].iter().map(|word| word.count_ones() as usize).sum();
+                let followed_by = ancestors.iter().filter(|ancestors| ancestors[i / 64] & (1 << (i % 64)) != 0).count();
+                follows + followed_by < n - 1
+            })
+            .count()
+    }
+
+    pub fn render(&self, order: &[usize]) -> Vec<u8> {
+        let order = self.repair(order);
+        let mut buf = self.prefix.clone();
+        for (i, unit) in order.iter().enumerate() {
+            if i > 0 {
+                buf.extend_from_slice(&self.separator);
+            }
+            buf.extend_from_slice(&self.units[*unit]);
+        }
+        buf.extend_from_slice(&self.suffix);
+        buf
+    }
+
+    /// Compressed size of the units laid out in `order`.
+    pub fn size(&self, order: &[usize]) -> usize {
+        compressed_len(&self.render(order), self.quality)
+    }
+}
+
+/// `before` for units where `ordered(j, i)` says whether unit `j`, which
+/// comes first in the source, has to stay ahead of unit `i`.
+pub fn constraints(n: usize, ordered: impl Fn(usize, usize) -> bool) -> Vec<Vec<usize>> {
+    (0..n).map(|i| (0..i).filter(|j| ordered(*j, i)).collect()).collect()
+}
+
+/// `before` for units that can go anywhere among the other free ones, while
+/// the rest stay where they are.
+pub fn free_constraints(free: &[bool]) -> Vec<Vec<usize>> {
+    constraints(free.len(), |j, i| !(free[j] && free[i]))
+}
+
+/// Whether `before` leaves nothing but the source order, which is exactly
+/// when every unit has to follow the one right before it.
+pub fn forced(before: &[Vec<usize>]) -> bool {
+    (1..before.len()).all(|i| before[i].contains(&(i - 1)))
+}
+
+pub fn compressed_len(buf: &[u8], quality: u32) -> usize {
+    let len = buf.len();
+    let mut input = brotli::CompressorReader::new(buf, len, quality, len as u32);
+    let mut compressed_bytes = vec![];
+
+    match input.read_to_end(&mut compressed_bytes) {
+        Ok(val) => val,
+        Err(e) => panic!("got error compressing {}", e)
+    }
+}
+
+pub fn unit_simhashes(units: &[Vec<u8>]) -> Vec<u64> {
+    units
+        .iter()
+        .map(|unit| simhash::simhash(&String::from_utf8_lossy(unit)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{forced, free_constraints, Problem};
+
+    fn problem(before: Vec<Vec<usize>>) -> Problem {
+        let units = (0..before.len().max(3)).map(|i| vec![b'a' + i as u8]).collect();
+        Problem { prefix: vec![], units, separator: vec![], suffix: vec![], quality: 11, before }
+    }
+
+    #[test]
+    fn repair_leaves_unconstrained_orders_alone() {
+        assert_eq!(problem(vec![]).repair(&[2, 0, 1]), vec![2, 0, 1]);
+    }
+
+    #[test]
+    fn repair_takes_units_as_soon_as_allowed() {
+        let problem = problem(vec![vec![], vec![0], vec![]]);
+        assert_eq!(problem.repair(&[1, 2, 0]), vec![2, 0, 1]);
+        assert_eq!(problem.repair(&[2, 0, 1]), vec![2, 0, 1]);
+        assert_eq!(problem.render(&[1, 2, 0]), b"cab".to_vec());
+    }
+
+    #[test]
+    fn repair_follows_chains() {
+        let problem = problem(vec![vec![], vec![0], vec![1], vec![]]);
+        assert_eq!(problem.repair(&[2, 1, 3, 0]), vec![3, 0, 1, 2]);
+    }
+
+    #[test]
+    fn pinned_units_dont_count_as_movable() {
+        // 1 stays between the free units 0 and 2, and 3 after all of them
+        let problem = problem(free_constraints(&[true, false, true, true]));
+        assert_eq!(problem.movable(), 2);
+        assert!(forced(&free_constraints(&[true, false, true])));
+        assert!(!forced(&free_constraints(&[true, true, false])));
+    }
+}
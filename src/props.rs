@@ -0,0 +1,126 @@
+use swc_ecma_ast::{Expr, Lit, Prop, PropName, PropOrSpread};
+
+use crate::baselines::prop_name;
+use crate::problem;
+use crate::purity;
+
+/// What has to stay ahead of what in an object literal, the way
+/// `Problem::before` takes it. Props are defined in order, so two with the
+/// same key keep theirs (the later one wins), and a value that runs code
+/// stays put, since even a plain variable read elsewhere could see it. Spreads copy whatever the source has at that point, computed keys
+/// that aren't literals get evaluated (and converted) in order, and
+/// `__proto__: x` sets the prototype rather than defining a prop, so those
+/// stay where they are. Empty if the props can go in any order.
+pub fn constraints(props: &[PropOrSpread]) -> Vec<Vec<usize>> {
+    let summaries: Vec<Option<Summary>> = props.iter().map(summarize).collect();
+    let before = problem::constraints(summaries.len(), |j, i| ordered(&summaries[j], &summaries[i]));
+    if before.iter().all(|before| before.is_empty()) {
+        return vec![];
+    }
+    before
+}
+
+struct Summary {
+    key: String,
+    runs_code: bool,
+}
+
+// None for props that stay put.
+fn summarize(prop: &PropOrSpread) -> Option<Summary> {
+    let PropOrSpread::Prop(prop) = prop else {
+        return None;
+    };
+    let (key, runs_code) = match &**prop {
+        Prop::Shorthand(ident) => (ident.sym.to_string(), false),
+        Prop::KeyValue(kv) => {
+            let key = match &kv.key {
+                PropName::Computed(computed) => literal_key(&computed.expr)?,
+                // only the non-computed form sets the prototype
+                key if prop_key_is_proto(key) => return None,
+                key => prop_name(key)?,
+            };
+            (key, !purity::pure_expr(&kv.value))
+        }
+        Prop::Getter(getter) => (method_key(&getter.key)?, false),
+        Prop::Setter(setter) => (method_key(&setter.key)?, false),
+        Prop::Method(method) => (method_key(&method.key)?, false),
+        Prop::Assign(_) => return None,
+    };
+    Some(Summary { key, runs_code })
+}
+
+fn prop_key_is_proto(key: &PropName) -> bool {
+    prop_name(key).is_some_and(|name| name == "__proto__")
+}
+
+fn method_key(key: &PropName) -> Option<String> {
+    match key {
+        PropName::Computed(computed) => literal_key(&computed.expr),
+        key => prop_name(key),
+    }
+}
+
+// The key a computed key's expression converts to, if it's a literal that
+// does so without running anything.
+fn literal_key(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Lit(Lit::Str(str)) => Some(str.value.to_string()),
+        Expr::Lit(Lit::Num(num)) => Some(num.value.to_string()),
+        _ => None,
+    }
+}
+
+// Whether `a`, which comes first, has to stay ahead of `b`.
+fn ordered(a: &Option<Summary>, b: &Option<Summary>) -> bool {
+    let (Some(a), Some(b)) = (a, b) else {
+        return true;
+    };
+    a.key == b.key || a.runs_code || b.runs_code
+}
+
+#[cfg(test)]
+mod tests {
+    use swc_ecma_ast::{Decl, Expr, ModuleItem, Stmt};
+
+    fn constraints(source: &str) -> Vec<Vec<usize>> {
+        let module = crate::parse_source(source);
+        let init = match &module.body[..] {
+            [ModuleItem::Stmt(Stmt::Decl(Decl::Var(var)))] => var.decls[0].init.as_deref(),
+            _ => None,
+        };
+        let Some(Expr::Object(lit)) = init else {
+            panic!("expected one object literal in {}", source);
+        };
+        super::constraints(&lit.props)
+    }
+
+    #[test]
+    fn plain_props_are_free() {
+        assert!(constraints(r#"const o = {a: 1, b: "x", c, d() {}, get e() { return 1 }, ["f"]: 2};"#).is_empty());
+    }
+
+    #[test]
+    fn duplicate_keys_keep_their_order() {
+        assert_eq!(constraints("const o = {a: 1, b: 2, a: 3};"), vec![vec![], vec![], vec![0]]);
+        // a literal computed key is the same key
+        assert_eq!(constraints(r#"const o = {a: 1, ["a"]: 2};"#), vec![vec![], vec![0]]);
+    }
+
+    #[test]
+    fn code_running_values_stay_put() {
+        // moving c ahead of a would read n before it's set
+        let before = constraints(r#"const o = {a: (n = 1, "zz"), b: "xyxyxy", c: n, d: "xyxyxy"};"#);
+        assert_eq!(before, vec![vec![], vec![0], vec![0], vec![0]]);
+        assert_eq!(constraints("const o = {a: 1, b: f(), c: 2};"), vec![vec![], vec![0], vec![1]]);
+    }
+
+    #[test]
+    fn spreads_computed_keys_and_proto_stay_put() {
+        assert_eq!(constraints("const o = {a: 1, ...b, c: 2};"), vec![vec![], vec![0], vec![1]]);
+        assert_eq!(constraints("const o = {a: 1, [k]: 2, c: 3};"), vec![vec![], vec![0], vec![1]]);
+        assert_eq!(constraints("const o = {a: 1, __proto__: p, c: 3};"), vec![vec![], vec![0], vec![1]]);
+        // the computed form defines an ordinary prop
+        assert!(constraints(r#"const o = {a: 1, ["__proto__"]: p, c: 3};"#).is_empty());
+    }
+}
+
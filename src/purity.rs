@@ -0,0 +1,84 @@
+use swc_ecma_ast::{BinaryOp, Class, ClassMember, Expr, Lit, Prop, PropName, PropOrSpread, UnaryOp};
+
+/// Whether evaluating `expr` can't run user code or otherwise be observed,
+/// so moving it relative to other pure code changes nothing. Reading a
+/// variable counts as pure; anything that could reach a getter, a
+/// `valueOf`/`toString` or an iterator doesn't.
+pub fn pure_expr(expr: &Expr) -> bool {
+    match expr {
+        Expr::This(_) | Expr::Ident(_) | Expr::Lit(_) | Expr::Fn(_) | Expr::Arrow(_) => true,
+        Expr::Paren(paren) => pure_expr(&paren.expr),
+        Expr::Seq(seq) => seq.exprs.iter().all(|expr| pure_expr(expr)),
+        Expr::Tpl(tpl) => tpl.exprs.iter().all(|expr| literal(expr)),
+        Expr::Array(array) => array.elems.iter().flatten().all(|elem| elem.spread.is_none() && pure_expr(&elem.expr)),
+        Expr::Object(object) => object.props.iter().all(pure_prop),
+        Expr::Class(class) => pure_class(&class.class),
+        Expr::Cond(cond) => pure_expr(&cond.test) && pure_expr(&cond.cons) && pure_expr(&cond.alt),
+        Expr::Unary(unary) => match unary.op {
+            UnaryOp::TypeOf | UnaryOp::Void | UnaryOp::Bang => pure_expr(&unary.arg),
+            // these coerce, which only literals do without side effects
+            UnaryOp::Minus | UnaryOp::Plus | UnaryOp::Tilde => literal(&unary.arg),
+            UnaryOp::Delete => false,
+        },
+        Expr::Bin(bin) => match bin.op {
+            BinaryOp::EqEqEq | BinaryOp::NotEqEq | BinaryOp::LogicalAnd | BinaryOp::LogicalOr | BinaryOp::NullishCoalescing => {
+                pure_expr(&bin.left) && pure_expr(&bin.right)
+            }
+            BinaryOp::In | BinaryOp::InstanceOf => false,
+            _ => literal(&bin.left) && literal(&bin.right),
+        },
+        _ => false,
+    }
+}
+
+// Literals and negated number literals, which coerce without calling anything.
+fn literal(expr: &Expr) -> bool {
+    match expr {
+        Expr::Lit(Lit::Regex(_)) => false,
+        Expr::Lit(_) => true,
+        Expr::Paren(paren) => literal(&paren.expr),
+        Expr::Unary(unary) => matches!(unary.op, UnaryOp::Minus | UnaryOp::Plus) && literal(&unary.arg),
+        _ => false,
+    }
+}
+
+fn pure_prop(prop: &PropOrSpread) -> bool {
+    let prop = match prop {
+        // spreading runs getters
+        PropOrSpread::Spread(_) => return false,
+        PropOrSpread::Prop(prop) => prop,
+    };
+    match &**prop {
+        Prop::Shorthand(_) => true,
+        Prop::KeyValue(kv) => pure_key(&kv.key) && pure_expr(&kv.value),
+        Prop::Getter(getter) => pure_key(&getter.key),
+        Prop::Setter(setter) => pure_key(&setter.key),
+        Prop::Method(method) => pure_key(&method.key),
+        Prop::Assign(_) => false,
+    }
+}
+
+// A computed key gets converted to a string, so it has to be a literal.
+fn pure_key(key: &PropName) -> bool {
+    match key {
+        PropName::Computed(computed) => literal(&computed.expr),
+        _ => true,
+    }
+}
+
+/// Whether defining the class runs nothing: the superclass, computed keys and
+/// static initializers are evaluated right away, everything else later.
+pub fn pure_class(class: &Class) -> bool {
+    if !class.decorators.is_empty() || class.super_class.as_deref().is_some_and(|super_class| !pure_expr(super_class)) {
+        return false;
+    }
+    class.body.iter().all(|member| match member {
+        ClassMember::Method(method) => pure_key(&method.key),
+        ClassMember::ClassProp(prop) => {
+            pure_key(&prop.key) && (!prop.is_static || prop.value.as_deref().is_none_or(pure_expr))
+        }
+        ClassMember::PrivateProp(prop) => !prop.is_static || prop.value.as_deref().is_none_or(pure_expr),
+        ClassMember::StaticBlock(_) => false,
+        ClassMember::Constructor(_) | ClassMember::PrivateMethod(_) | ClassMember::TsIndexSignature(_) | ClassMember::Empty(_) => true,
+    })
+}
@@ -0,0 +1,83 @@
+use std::ops::Range;
+
+use swc_ecma_ast::{ExportSpecifier, ImportDecl, ImportNamedSpecifier, ImportSpecifier, ModuleExportName, NamedExport};
+
+/// The named specifiers of an import, which come after any default one.
+/// Imported bindings all exist before the module body runs, and nothing
+/// records the order they were listed in, so any order behaves the same.
+pub fn imported(decl: &ImportDecl) -> Range<usize> {
+    let start = decl.specifiers.iter().take_while(|spec| !matches!(spec, ImportSpecifier::Named(_))).count();
+    start..decl.specifiers.len()
+}
+
+/// The named specifiers of an export list. A module namespace object sorts
+/// its keys, so the order they're exported in can't be seen either.
+pub fn exported(export: &NamedExport) -> Range<usize> {
+    let start = export.specifiers.iter().take_while(|spec| !matches!(spec, ExportSpecifier::Named(_))).count();
+    start..export.specifiers.len()
+}
+
+/// A label per named import for manifests: the local name it binds.
+pub fn import_keys(specifiers: &[ImportSpecifier]) -> Vec<String> {
+    specifiers.iter().map(|spec| named(spec).local.sym.to_string()).collect()
+}
+
+/// One of the specifiers in `imported`'s range.
+pub fn named(spec: &ImportSpecifier) -> &ImportNamedSpecifier {
+    match spec {
+        ImportSpecifier::Named(named) => named,
+        _ => unreachable!("only named specifiers get reordered"),
+    }
+}
+
+/// A label per named export for manifests: the name it's exported as.
+pub fn export_keys(specifiers: &[ExportSpecifier]) -> Vec<String> {
+    specifiers
+        .iter()
+        .map(|spec| match spec {
+            ExportSpecifier::Named(named) => export_name(named.exported.as_ref().unwrap_or(&named.orig)),
+            _ => unreachable!("only named specifiers get reordered"),
+        })
+        .collect()
+}
+
+fn export_name(name: &ModuleExportName) -> String {
+    match name {
+        ModuleExportName::Ident(ident) => ident.sym.to_string(),
+        ModuleExportName::Str(str) => str.value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use swc_ecma_ast::{ModuleDecl, ModuleItem};
+
+    fn decl(source: &str) -> ModuleDecl {
+        match crate::parse_source(source).body.remove(0) {
+            ModuleItem::ModuleDecl(decl) => decl,
+            _ => panic!("expected an import or export in {}", source),
+        }
+    }
+
+    #[test]
+    fn defaults_and_namespaces_stay_in_front() {
+        let ModuleDecl::Import(import) = decl("import d, {b, a as c} from 'm';") else { unreachable!() };
+        let range = super::imported(&import);
+        assert_eq!(range, 1..3);
+        assert_eq!(super::import_keys(&import.specifiers[range]), vec!["b", "c"]);
+
+        let ModuleDecl::Import(import) = decl("import * as ns from 'm';") else { unreachable!() };
+        assert!(super::imported(&import).is_empty());
+    }
+
+    #[test]
+    fn exports_are_labelled_by_exported_name() {
+        let ModuleDecl::ExportNamed(export) = decl("export {b, a as c, d as 'e f'};") else { unreachable!() };
+        let range = super::exported(&export);
+        assert_eq!(range, 0..3);
+        assert_eq!(super::export_keys(&export.specifiers[range]), vec!["b", "c", "e f"]);
+
+        let ModuleDecl::ExportNamed(export) = decl("export * as ns, {a, b} from 'm';") else { unreachable!() };
+        assert_eq!(super::exported(&export), 1..3);
+    }
+}
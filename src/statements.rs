@@ -0,0 +1,190 @@
+use std::collections::HashSet;
+
+use swc_common::sync::Lrc;
+use swc_common::SourceMap;
+use swc_ecma_ast::{Callee, Decl, Expr, Ident, Module, ModuleItem, Pat, Stmt, WithStmt};
+use swc_ecma_visit::{Visit, VisitWith};
+
+use crate::problem;
+use crate::purity;
+
+/// What has to stay ahead of what in a statement list: `before[i]` lists
+/// the statements that must come before statement i, and any order that
+/// respects that behaves the same. Only declarations move: variable
+/// declarations of plain names with pure initializers, classes that run
+/// nothing when defined, and function declarations, which are hoisted.
+/// Anything else keeps its place relative to everything. Two declarations
+/// stay in order if they declare the same name or, unless one is a function
+/// declaration, if either mentions a name the other declares, which covers
+/// reads that would hit the temporal dead zone.
+/// None if no two statements can trade places.
+pub fn constraints<T>(items: &[T], stmt: impl Fn(&T) -> Option<&Stmt>) -> Option<Vec<Vec<usize>>> {
+    let summaries: Vec<Summary> = items.iter().map(|item| stmt(item).map_or_else(Summary::fixed, summarize)).collect();
+    let before = problem::constraints(summaries.len(), |j, i| ordered(&summaries[j], &summaries[i]));
+    if problem::forced(&before) {
+        return None;
+    }
+    Some(before)
+}
+
+struct Summary {
+    movable: bool,
+    hoisted: bool,
+    declared: Vec<String>,
+    // every identifier in the statement, including property names and
+    // shadowed ones, which only ever adds constraints
+    mentioned: HashSet<String>,
+}
+
+impl Summary {
+    fn fixed() -> Summary {
+        Summary { movable: false, hoisted: false, declared: vec![], mentioned: HashSet::new() }
+    }
+}
+
+fn summarize(stmt: &Stmt) -> Summary {
+    let mut mentioned = Mentioned::default();
+    stmt.visit_with(&mut mentioned);
+    let (movable, hoisted, declared) = match stmt {
+        Stmt::Decl(Decl::Var(var)) => {
+            let names: Option<Vec<String>> = var
+                .decls
+                .iter()
+                .map(|decl| match &decl.name {
+                    Pat::Ident(ident) if decl.init.as_deref().is_none_or(purity::pure_expr) => Some(ident.id.sym.to_string()),
+                    _ => None,
+                })
+                .collect();
+            match names {
+                Some(names) => (true, false, names),
+                None => return Summary::fixed(),
+            }
+        }
+        Stmt::Decl(Decl::Fn(decl)) => (true, true, vec![decl.ident.sym.to_string()]),
+        Stmt::Decl(Decl::Class(decl)) => (purity::pure_class(&decl.class), false, vec![decl.ident.sym.to_string()]),
+        _ => return Summary::fixed(),
+    };
+    Summary { movable, hoisted, declared, mentioned: mentioned.names }
+}
+
+// Whether `a`, which comes first, has to stay ahead of `b`.
+fn ordered(a: &Summary, b: &Summary) -> bool {
+    if !a.movable || !b.movable || a.declared.iter().any(|name| b.declared.contains(name)) {
+        return true;
+    }
+    // a function exists from the start of the scope and its body only runs
+    // when called, which only fixed statements do
+    if a.hoisted || b.hoisted {
+        return false;
+    }
+    a.declared.iter().any(|name| b.mentioned.contains(name)) || b.declared.iter().any(|name| a.mentioned.contains(name))
+}
+
+/// Every identifier in `expr`, counted the same way as for statements.
+pub fn mentioned(expr: &Expr) -> HashSet<String> {
+    let mut mentioned = Mentioned::default();
+    expr.visit_with(&mut mentioned);
+    mentioned.names
+}
+
+#[derive(Default)]
+struct Mentioned {
+    names: HashSet<String>,
+}
+
+impl Visit for Mentioned {
+    fn visit_ident(&mut self, ident: &Ident) {
+        self.names.insert(ident.sym.to_string());
+    }
+}
+
+/// Whether the module uses direct `eval` or `with` anywhere, either of which
+/// can make any name refer to anything, so no dependency analysis holds.
+pub fn dynamic_scope(module: &Module) -> bool {
+    let mut finder = DynamicScope::default();
+    module.visit_with(&mut finder);
+    finder.found
+}
+
+#[derive(Default)]
+struct DynamicScope {
+    found: bool,
+}
+
+impl Visit for DynamicScope {
+    fn visit_with_stmt(&mut self, _: &WithStmt) {
+        self.found = true;
+    }
+
+    fn visit_callee(&mut self, callee: &Callee) {
+        if let Callee::Expr(expr) = callee {
+            if matches!(&**expr, Expr::Ident(ident) if &*ident.sym == "eval") {
+                self.found = true;
+            }
+        }
+        callee.visit_children_with(self);
+    }
+}
+
+/// A label per statement for manifests: the names it declares, or a hash of
+/// its minified source if it doesn't declare any.
+pub fn keys<T: swc_ecma_codegen::Node>(items: &[T], stmt: impl Fn(&T) -> Option<&Stmt>, cm: &Lrc<SourceMap>) -> Vec<String> {
+    items
+        .iter()
+        .map(|item| {
+            let declared = stmt(item).map(summarize).map(|summary| summary.declared).unwrap_or_default();
+            if declared.is_empty() {
+                format!("#{:016x}", crate::checkpoint::content_hash(&crate::ast_bytes(item, cm)))
+            } else {
+                declared.join(",")
+            }
+        })
+        .collect()
+}
+
+pub fn stmt(stmt: &Stmt) -> Option<&Stmt> {
+    Some(stmt)
+}
+
+pub fn item_stmt(item: &ModuleItem) -> Option<&Stmt> {
+    match item {
+        ModuleItem::Stmt(stmt) => Some(stmt),
+        ModuleItem::ModuleDecl(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{constraints, item_stmt};
+
+    fn before(source: &str) -> Option<Vec<Vec<usize>>> {
+        constraints(&crate::parse_source(source).body, item_stmt)
+    }
+
+    #[test]
+    fn reads_stay_after_what_they_read() {
+        // b would read a in its temporal dead zone if it went first
+        let before = before("const a = 1; const b = a; const c = 2;").unwrap();
+        assert_eq!(before, vec![vec![], vec![0], vec![]]);
+    }
+
+    #[test]
+    fn function_declarations_are_hoisted() {
+        let before = before("const a = f; function f() { return a; } const b = 2;").unwrap();
+        assert_eq!(before, vec![Vec::<usize>::new(); 3]);
+    }
+
+    #[test]
+    fn same_names_stay_in_order() {
+        let vars = before("var x = 1; var y = 2; var x = 3;").unwrap();
+        assert_eq!(vars, vec![vec![], vec![], vec![0]]);
+        let functions = before("function g() { return 1; } function h() {} function g() { return 2; }").unwrap();
+        assert_eq!(functions, vec![vec![], vec![], vec![0]]);
+    }
+
+    #[test]
+    fn code_that_runs_keeps_its_place() {
+        assert_eq!(before("const a = 1; const b = 2; log(a);"), Some(vec![vec![], vec![], vec![0, 1]]));
+        assert_eq!(before("const a = 1; log(a); const b = 2;"), None);
+    }
+}
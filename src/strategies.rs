@@ -0,0 +1,304 @@
+use std::time::Instant;
+
+use metaheuristics::Metaheuristics;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::portfolio::Search;
+use crate::problem;
+use crate::traveling_salesman;
+
+const ANNEALING_START_TEMPERATURE: f64 = 2.0;
+const POPULATION_SIZE: usize = 16;
+const TOURNAMENT_SIZE: usize = 3;
+
+/// Ways of searching for a small order. All of them measure candidates by
+/// real compressed size and report improvements to the shared search.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Strategy {
+    HillClimbing,
+    Annealing,
+    Tsp,
+    Genetic,
+}
+
+impl Strategy {
+    pub const ALL: [Strategy; 4] = [Strategy::HillClimbing, Strategy::Annealing, Strategy::Tsp, Strategy::Genetic];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Strategy::HillClimbing => "hill",
+            Strategy::Annealing => "anneal",
+            Strategy::Tsp => "tsp",
+            Strategy::Genetic => "ga",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Strategy> {
+        Strategy::ALL.into_iter().find(|strategy| strategy.name() == name)
+    }
+
+    /// Searches until the budget runs out or it's `until`, carrying on from
+    /// `state` or, without one, starting from the best order found so far.
+    /// Returns where it got to, to carry on from next time.
+    pub fn run(self, search: &Search, state: Option<State>, rng: &mut impl Rng, until: Instant) -> State {
+        let stop = || search.done() || Instant::now() >= until;
+        let best = search.best();
+        match (self, state) {
+            (Strategy::HillClimbing, Some(State::Walk { order, size })) => climb(self, search, &stop, order, size, rng, tweak),
+            (Strategy::HillClimbing, _) => climb(self, search, &stop, best.order, best.size, rng, tweak),
+            (Strategy::Annealing, Some(State::Anneal { order, size, temperature })) => anneal(search, &stop, order, size, temperature, rng),
+            (Strategy::Annealing, _) => anneal(search, &stop, best.order, best.size, ANNEALING_START_TEMPERATURE, rng),
+            (Strategy::Tsp, Some(State::Walk { order, size })) => climb(self, search, &stop, order, size, rng, reverse_segment),
+            (Strategy::Tsp, _) => tsp(search, &stop, rng),
+            (Strategy::Genetic, Some(State::Population(population))) => genetic(search, &stop, population, rng),
+            (Strategy::Genetic, _) => genetic(search, &stop, vec![(best.size, best.order)], rng),
+        }
+    }
+}
+
+/// Where a strategy's search is at, so it can be run a slice at a time and
+/// picked up again after a checkpoint.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum State {
+    // hill climbing and tsp polishing its tour: the order they're at
+    Walk { order: Vec<usize>, size: usize },
+    // and the temperature annealing started cooling from
+    Anneal { order: Vec<usize>, size: usize, temperature: f64 },
+    // sizes and orders
+    Population(Vec<(usize, Vec<usize>)>),
+}
+
+impl State {
+    /// This state for a new search over what's left of `search`'s budget:
+    /// annealing starts that one as cool as it's got in this one.
+    pub fn resumable(&self, search: &Search) -> State {
+        match self {
+            State::Anneal { order, size, temperature } => {
+                State::Anneal { order: order.clone(), size: *size, temperature: temperature * (1.0 - search.budget.portion_used()) }
+            }
+            state => state.clone(),
+        }
+    }
+}
+
+// A random small edit: swap two units, move one, or reverse a segment.
+fn tweak(order: &[usize], rng: &mut impl Rng) -> Vec<usize> {
+    let mut next = order.to_vec();
+    let first = rng.gen_range(0..next.len());
+    let second = rng.gen_range(0..next.len());
+    match rng.gen_range(0..3) {
+        0 => next.swap(first, second),
+        1 => {
+            let unit = next.remove(first);
+            next.insert(second, unit);
+        }
+        _ => reverse_between(&mut next, first, second),
+    }
+    next
+}
+
+fn reverse_segment(order: &[usize], rng: &mut impl Rng) -> Vec<usize> {
+    let mut next = order.to_vec();
+    let first = rng.gen_range(0..next.len());
+    let second = rng.gen_range(0..next.len());
+    reverse_between(&mut next, first, second);
+    next
+}
+
+fn reverse_between(order: &mut [usize], first: usize, second: usize) {
+    let (lo, hi) = if first < second { (first, second) } else { (second, first) };
+    order[lo..=hi].reverse();
+}
+
+fn climb<R: Rng>(
+    strategy: Strategy,
+    search: &Search,
+    stop: &dyn Fn() -> bool,
+    mut order: Vec<usize>,
+    mut size: usize,
+    rng: &mut R,
+    step: fn(&[usize], &mut R) -> Vec<usize>,
+) -> State {
+    while !stop() {
+        let next = step(&order, rng);
+        let next_size = search.size(&next);
+        // accept sideways moves too so we can drift across plateaus
+        if next_size <= size {
+            if next_size < size {
+                search.offer(&next, next_size, strategy);
+            }
+            order = next;
+            size = next_size;
+        }
+    }
+    State::Walk { order, size }
+}
+
+fn anneal(search: &Search, stop: &dyn Fn() -> bool, mut order: Vec<usize>, mut size: usize, start_temperature: f64, rng: &mut impl Rng) -> State {
+    while !stop() {
+        // cools down over whichever limit runs out first
+        let temperature = start_temperature * (1.0 - search.budget.portion_used());
+
+        let next = tweak(&order, rng);
+        let next_size = search.size(&next);
+        let accept = next_size <= size
+            || rng.gen_range(0.0..1.0) < (-((next_size - size) as f64) / temperature).exp();
+        if accept {
+            search.offer(&next, next_size, Strategy::Annealing);
+            order = next;
+            size = next_size;
+        }
+    }
+    State::Anneal { order, size, temperature: start_temperature }
+}
+
+// Solves a TSP over simhash distances between units as a cheap proxy for
+// "similar things next to each other", then polishes the tour with 2-opt
+// style segment reversals measured by real compressed size.
+fn tsp(search: &Search, stop: &dyn Fn() -> bool, rng: &mut impl Rng) -> State {
+    let simhashes = problem::unit_simhashes(&search.problem.units);
+    let distance_matrix: Vec<Vec<f64>> = simhashes
+        .iter()
+        .map(|a| simhashes.iter().map(|b| simhash::hamming_distance(*a, *b) as f64).collect())
+        .collect();
+
+    // climbs the proxy for a tenth of the time left, or less if the search
+    // is called off first, even if that's longer than the slice
+    let proxy_time = search.budget.remaining_time() / 10;
+    let start = Instant::now();
+    let mut salesman = traveling_salesman::TravellingSalesman { distance_matrix: &distance_matrix, rng: &mut *rng };
+    let mut tour = salesman.generate_candidate();
+    let mut tour_rank = salesman.rank_candidate(&tour);
+    while start.elapsed() < proxy_time {
+        if search.done() {
+            let best = search.best();
+            return State::Walk { order: best.order, size: best.size };
+        }
+        let next = salesman.tweak_candidate(&tour);
+        let next_rank = salesman.rank_candidate(&next);
+        if next_rank >= tour_rank {
+            tour = next;
+            tour_rank = next_rank;
+        }
+    }
+
+    // the route is a cycle back to its first city; cut it at its longest edge
+    let mut route = tour.route;
+    route.pop();
+    let cut = (0..route.len())
+        .max_by(|a, b| {
+            let edge = |i: usize| distance_matrix[route[i]][route[(i + 1) % route.len()]];
+            edge(*a).total_cmp(&edge(*b))
+        })
+        .unwrap();
+    route.rotate_left(cut + 1);
+
+    let size = search.size(&route);
+    search.offer(&route, size, Strategy::Tsp);
+    climb(Strategy::Tsp, search, stop, route, size, rng, reverse_segment)
+}
+
+fn genetic(search: &Search, stop: &dyn Fn() -> bool, mut population: Vec<(usize, Vec<usize>)>, rng: &mut impl Rng) -> State {
+    while population.len() < POPULATION_SIZE {
+        if stop() {
+            return State::Population(population);
+        }
+        let order = tweak(&population[0].1, rng);
+        let size = search.size(&order);
+        search.offer(&order, size, Strategy::Genetic);
+        population.push((size, order));
+    }
+
+    while !stop() {
+        let first = tournament(&population, rng);
+        let second = tournament(&population, rng);
+        let child = tweak(&order_crossover(first, second, rng), rng);
+        let size = search.size(&child);
+        search.offer(&child, size, Strategy::Genetic);
+
+        let worst = (0..population.len()).max_by_key(|i| population[*i].0).unwrap();
+        if size < population[worst].0 {
+            population[worst] = (size, child);
+        }
+    }
+    State::Population(population)
+}
+
+fn tournament<'a>(population: &'a [(usize, Vec<usize>)], rng: &mut impl Rng) -> &'a [usize] {
+    let winner = (0..TOURNAMENT_SIZE)
+        .map(|_| &population[rng.gen_range(0..population.len())])
+        .min_by_key(|(size, _)| *size)
+        .unwrap();
+    &winner.1
+}
+
+// OX1: keep a random slice of the first parent in place and fill the other
+// positions with the remaining units in the second parent's order.
+fn order_crossover(first: &[usize], second: &[usize], rng: &mut impl Rng) -> Vec<usize> {
+    let n = first.len();
+    let a = rng.gen_range(0..n);
+    let b = rng.gen_range(0..n);
+    let (lo, hi) = if a < b { (a, b) } else { (b, a) };
+
+    let mut used = vec![false; n];
+    for unit in &first[lo..=hi] {
+        used[*unit] = true;
+    }
+    let mut rest = second.iter().filter(|unit| !used[**unit]);
+    (0..n)
+        .map(|i| if (lo..=hi).contains(&i) { first[i] } else { *rest.next().unwrap() })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::{State, Strategy, POPULATION_SIZE};
+    use crate::budget::Limits;
+    use crate::portfolio::{Best, Search};
+    use crate::problem::Problem;
+
+    #[test]
+    fn strategies_carry_on_from_where_a_slice_left_off() {
+        let units = (0..6).map(|i| format!("unit{}", i % 3).into_bytes()).collect();
+        let problem = Problem { prefix: vec![], units, separator: b",".to_vec(), suffix: vec![], quality: 11, before: vec![] };
+        let limits = Limits { time: Duration::from_secs(60), evaluations: Some(20), patience: None, min_bytes_per_minute: None };
+        let order: Vec<usize> = (0..6).collect();
+        let search = Search::new(&problem, Best { size: problem.size(&order), order, found_by: "original" }, &limits);
+        let mut rng = StdRng::seed_from_u64(0);
+
+        // a slice that's already over leaves the population at just the seed
+        let state = Strategy::Genetic.run(&search, None, &mut rng, Instant::now());
+        assert!(matches!(&state, State::Population(population) if population.len() == 1));
+        assert_eq!(search.budget.evaluations(), 0);
+        let state = Strategy::Genetic.run(&search, Some(state), &mut rng, Instant::now() + Duration::from_secs(60));
+        assert!(matches!(&state, State::Population(population) if population.len() == POPULATION_SIZE));
+        assert_eq!(search.budget.evaluations(), 20);
+    }
+
+    #[test]
+    fn states_resume_where_they_left_off() {
+        let units = (0..4).map(|i| format!("unit{}", i).into_bytes()).collect();
+        let problem = Problem { prefix: vec![], units, separator: b",".to_vec(), suffix: vec![], quality: 11, before: vec![] };
+        let limits = Limits { time: Duration::from_secs(60), evaluations: Some(10), patience: None, min_bytes_per_minute: None };
+        let order: Vec<usize> = (0..4).collect();
+        let walk = State::Walk { order: vec![3, 2, 1, 0], size: 1 };
+        let search = Search::new(&problem, Best { size: problem.size(&order), order, found_by: "original" }, &limits)
+            .resuming(vec![(Strategy::HillClimbing, walk)]);
+        // nobody picked it up yet, so a checkpoint would keep it
+        assert!(matches!(&search.states()[..], [(Strategy::HillClimbing, State::Walk { .. })]));
+
+        for _ in 0..4 {
+            search.size(&[0, 1, 2, 3]);
+        }
+        // 4 of 10 evaluations in, annealing is 40% cooler
+        let anneal = State::Anneal { order: vec![0, 1, 2, 3], size: 1, temperature: 2.0 };
+        let State::Anneal { temperature, .. } = anneal.resumable(&search) else { unreachable!() };
+        assert!((temperature - 1.2).abs() < 1e-9, "{}", temperature);
+    }
+}
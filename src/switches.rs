@@ -0,0 +1,107 @@
+use std::collections::HashSet;
+
+use swc_ecma_ast::{Decl, Expr, Lit, Stmt, SwitchCase, SwitchStmt};
+
+/// What has to stay ahead of what in a switch's cases, the way
+/// `Problem::before` takes it. Cases are tried in order, which only shows if
+/// a test runs code or two tests match the same value, so every test has to
+/// be a distinct literal. Execution falls through from a case into the next
+/// one unless it ends in `break`, `continue`, `return` or `throw`, so every
+/// case has to, except the last one, which stays last. A default is only
+/// reached after every test fails wherever it is. Function declarations are
+/// left alone since a later one wins over an earlier one with the same name.
+/// None if the cases can't be reordered.
+pub fn constraints(switch: &SwitchStmt) -> Option<Vec<Vec<usize>>> {
+    let cases = &switch.cases;
+    if cases.len() < 2 {
+        return None;
+    }
+    let mut tests = HashSet::new();
+    for case in cases {
+        if let Some(test) = &case.test {
+            if !tests.insert(constant(test)?) {
+                return None;
+            }
+        }
+        if case.cons.iter().any(|stmt| matches!(stmt, Stmt::Decl(Decl::Fn(_)))) {
+            return None;
+        }
+    }
+    let last = cases.len() - 1;
+    if !cases[..last].iter().all(|case| case.cons.last().is_some_and(abrupt)) {
+        return None;
+    }
+    let falls_off = !cases[last].cons.last().is_some_and(abrupt);
+    Some((0..cases.len()).map(|i| if i == last && falls_off { (0..last).collect() } else { vec![] }).collect())
+}
+
+// A literal test, as something to tell duplicates apart by. Regexes are
+// objects, so never equal to anything.
+fn constant(test: &Expr) -> Option<String> {
+    match test {
+        Expr::Lit(Lit::Str(str)) => Some(format!("{:?}", &*str.value)),
+        Expr::Lit(Lit::Num(num)) => Some(num.value.to_string()),
+        Expr::Lit(Lit::BigInt(big)) => Some(format!("{}n", big.value)),
+        Expr::Lit(Lit::Bool(bool)) => Some(bool.value.to_string()),
+        Expr::Lit(Lit::Null(_)) => Some("null".to_string()),
+        _ => None,
+    }
+}
+
+// Whether control never gets past the end of `stmt`.
+fn abrupt(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::Break(_) | Stmt::Continue(_) | Stmt::Return(_) | Stmt::Throw(_) => true,
+        Stmt::Block(block) => block.stmts.last().is_some_and(abrupt),
+        Stmt::If(stmt) => abrupt(&stmt.cons) && stmt.alt.as_deref().is_some_and(abrupt),
+        _ => false,
+    }
+}
+
+/// A label per case for manifests: its test, or "default".
+pub fn keys(cases: &[SwitchCase]) -> Vec<String> {
+    cases
+        .iter()
+        .map(|case| match &case.test {
+            Some(test) => constant(test).unwrap(),
+            None => "default".to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use swc_ecma_ast::{ModuleItem, Stmt, SwitchStmt};
+
+    use super::constraints;
+
+    fn switch(source: &str) -> SwitchStmt {
+        match crate::parse_source(source).body.remove(0) {
+            ModuleItem::Stmt(Stmt::Switch(switch)) => switch,
+            _ => panic!("not a switch"),
+        }
+    }
+
+    #[test]
+    fn cases_that_break_move_freely() {
+        let before = constraints(&switch("switch (x) { case 1: a(); break; case 2: b(); return; default: c(); break; }"));
+        assert_eq!(before, Some(vec![Vec::<usize>::new(); 3]));
+    }
+
+    #[test]
+    fn fallthrough_keeps_the_order() {
+        assert_eq!(constraints(&switch("switch (x) { case 1: a(); case 2: b(); break; }")), None);
+    }
+
+    #[test]
+    fn duplicate_tests_keep_the_order() {
+        assert_eq!(constraints(&switch("switch (x) { case 1: a(); break; case 1: b(); break; }")), None);
+        assert_eq!(constraints(&switch("switch (x) { case 'a': a(); break; case \"a\": b(); break; }")), None);
+    }
+
+    #[test]
+    fn last_case_falling_off_the_end_stays_last() {
+        let before = constraints(&switch("switch (x) { case 1: a(); break; case 2: b(); break; case 3: c(); }"));
+        assert_eq!(before, Some(vec![vec![], vec![], vec![0, 1]]));
+    }
+}
@@ -1,18 +1,17 @@
 use metaheuristics::Metaheuristics;
-use rand::rngs::ThreadRng;
 use rand::seq::SliceRandom;
 use rand::Rng;
 
-pub struct TravellingSalesman<'a> {
+pub struct TravellingSalesman<'a, R: Rng> {
     pub distance_matrix: &'a Vec<Vec<f64>>,
-    pub rng: &'a mut ThreadRng,
+    pub rng: &'a mut R,
 }
 
 pub struct Candidate {
     pub route: Vec<usize>,
 }
 
-impl<'a> Metaheuristics<Candidate> for TravellingSalesman<'a> {
+impl<'a, R: Rng> Metaheuristics<Candidate> for TravellingSalesman<'a, R> {
     fn clone_candidate(&mut self, candidate: &Candidate) -> Candidate {
         Candidate {
             route: candidate.route.clone(),
@@ -107,7 +106,7 @@ pub fn get_route_distance(distance_matrix: &[Vec<f64>], route: &[usize]) -> f64
     };
 
     route_iter.fold(0.0, |mut total_distance, &next_city| {
-        total_distance += distance_matrix[current_city as usize][next_city as usize];
+        total_distance += distance_matrix[current_city][next_city];
         current_city = next_city;
         total_distance
     })
@@ -0,0 +1,253 @@
+use std::path::Path;
+
+use simhash::hamming_distance;
+use swc_common::sync::Lrc;
+use swc_common::SourceMap;
+use swc_ecma_ast::{ArrayLit, Class, FnDecl, ImportDecl, ModuleItem, NamedExport, ObjectLit, ObjectPat, Stmt, SwitchStmt, VarDecl};
+use swc_ecma_visit::{Visit, VisitWith};
+
+use crate::arrays;
+use crate::declarators;
+use crate::eligible::{self, Eligible};
+use crate::functions;
+use crate::imports;
+use crate::manifest::{Entry, Manifest};
+use crate::members;
+use crate::options::Options;
+use crate::patterns;
+use crate::problem;
+use crate::specifiers;
+use crate::statements;
+use crate::switches;
+
+// a previous literal only counts as the same one if at least this share of
+// the keys carried over
+const MIN_KEY_OVERLAP: f64 = 0.5;
+
+/// Orders from a previous build, either its manifest (.json) or the output
+/// file itself, in which case every literal's current order is taken as the
+/// chosen one, for the same literals and arrays `options` picks out.
+pub fn load(path: &Path, options: &Options) -> Manifest {
+    if path.extension().is_some_and(|extension| extension == "json") {
+        return Manifest::load(path);
+    }
+    let cm: Lrc<SourceMap> = Default::default();
+    let (_, module, comments) = crate::parse(&cm, path);
+    let eligible = eligible::collect(&module, &comments, options.max_depth, &options.unordered);
+    let mut collector = Collector { cm: &cm, manifest: Manifest::default(), location: (0, 0), eligible };
+    module.visit_with(&mut collector);
+    collector.manifest
+}
+
+// Walks literals the same way `Compressor` does so paths line up.
+struct Collector<'a> {
+    cm: &'a Lrc<SourceMap>,
+    manifest: Manifest,
+    location: (usize, usize),
+    eligible: Eligible,
+}
+
+impl<'a> Visit for Collector<'a> {
+    fn visit_module_items(&mut self, items: &[ModuleItem]) {
+        for (i, item) in items.iter().enumerate() {
+            self.location = (i, 0);
+            item.visit_with(self);
+        }
+        self.add_runs(items, None, functions::item_decl);
+        self.add_statements(items, None, statements::item_stmt);
+        // which imports are free to move depends on options, so this takes
+        // every run
+        for run in imports::runs(items) {
+            let keys = imports::keys(&items[run]);
+            self.add(crate::imports_path(&keys), keys);
+        }
+    }
+
+    fn visit_stmts(&mut self, stmts: &[Stmt]) {
+        stmts.visit_children_with(self);
+        self.add_runs(stmts, Some(self.location.0), functions::stmt_decl);
+        self.add_statements(stmts, Some(self.location.0), statements::stmt);
+    }
+
+    fn visit_class(&mut self, class: &Class) {
+        class.visit_children_with(self);
+        if members::constraints(&class.body).is_some() {
+            let keys = members::keys(&class.body, self.cm);
+            self.add(crate::class_path(self.location.0, &keys), keys);
+        }
+    }
+
+    fn visit_array_lit(&mut self, arr: &ArrayLit) {
+        arr.visit_children_with(self);
+        if self.eligible.array(arr) && arrays::constraints(arr).is_some() {
+            let keys = arrays::keys(arr, self.cm);
+            self.add(crate::array_path(self.location.0, &keys), keys);
+        }
+    }
+
+    fn visit_switch_stmt(&mut self, switch: &SwitchStmt) {
+        switch.visit_children_with(self);
+        if switches::constraints(switch).is_some() {
+            let keys = switches::keys(&switch.cases);
+            self.add(crate::switch_path(self.location.0, &keys), keys);
+        }
+    }
+
+    fn visit_var_decl(&mut self, var: &VarDecl) {
+        var.visit_children_with(self);
+        if declarators::reorderable(var) {
+            let keys = declarators::keys(var);
+            self.add(crate::declarators_path(self.location.0, &keys), keys);
+        }
+    }
+
+    fn visit_object_pat(&mut self, pat: &ObjectPat) {
+        pat.visit_children_with(self);
+        if patterns::reorderable(pat) {
+            let keys = patterns::keys(pat);
+            self.add(crate::pattern_path(self.location.0, &keys), keys);
+        }
+    }
+
+    fn visit_import_decl(&mut self, decl: &ImportDecl) {
+        decl.visit_children_with(self);
+        let range = specifiers::imported(decl);
+        if range.len() >= 2 {
+            let keys = specifiers::import_keys(&decl.specifiers[range]);
+            self.add(format!("{}/import", self.location.0), keys);
+        }
+    }
+
+    fn visit_named_export(&mut self, export: &NamedExport) {
+        export.visit_children_with(self);
+        let range = specifiers::exported(export);
+        if range.len() >= 2 {
+            let keys = specifiers::export_keys(&export.specifiers[range]);
+            self.add(format!("{}/export", self.location.0), keys);
+        }
+    }
+
+    fn visit_object_lit(&mut self, lit: &ObjectLit) {
+        let path = format!("{}/{}", self.location.0, self.location.1);
+        self.location.1 += 1;
+        lit.visit_children_with(self);
+        if self.eligible.literal(lit) {
+            self.add(path, crate::manifest::prop_keys(&lit.props, self.cm));
+        }
+    }
+}
+
+impl<'a> Collector<'a> {
+    // Takes the order the target is in as the chosen one. Spans are only
+    // looked up within a run, so they're left out.
+    fn add(&mut self, path: String, keys: Vec<String>) {
+        let order = (0..keys.len()).collect();
+        self.manifest.literals.push(Entry { path, keys, order, size: 0, span: (0, 0) });
+    }
+
+    fn add_runs<T>(&mut self, items: &[T], item: Option<usize>, decl: impl Fn(&T) -> Option<&FnDecl>) {
+        for run in functions::runs(items, &decl) {
+            let keys = functions::names(&items[run], &decl);
+            self.add(crate::function_path(item, &keys), keys);
+        }
+    }
+
+    fn add_statements<T: swc_ecma_codegen::Node>(&mut self, items: &[T], item: Option<usize>, stmt: impl Fn(&T) -> Option<&Stmt>) {
+        if statements::constraints(items, &stmt).is_some() {
+            let keys = statements::keys(items, &stmt, self.cm);
+            self.add(crate::statements_path(item, &keys), keys);
+        }
+    }
+}
+
+/// Carries a previous order over to a literal whose keys may have changed
+/// since: keys that survived keep their relative order, and each new one
+/// goes right after the unit its simhash is closest to. None if no previous
+/// literal shares enough keys. Entries whose orders are broken are ignored.
+pub fn order(previous: &Manifest, path: &str, keys: &[String], units: &[Vec<u8>]) -> Option<Vec<usize>> {
+    let entry = previous
+        .literals
+        .iter()
+        .filter(|entry| entry.valid_order())
+        .map(|entry| (key_overlap(&entry.keys, keys), entry.path == path, entry))
+        .filter(|(overlap, _, _)| *overlap >= MIN_KEY_OVERLAP)
+        .max_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)))?
+        .2;
+
+    let mut placed = vec![false; keys.len()];
+    let mut order = vec![];
+    for i in &entry.order {
+        if let Some(position) = (0..keys.len()).find(|j| !placed[*j] && keys[*j] == entry.keys[*i]) {
+            placed[position] = true;
+            order.push(position);
+        }
+    }
+
+    let simhashes = problem::unit_simhashes(units);
+    for new in (0..keys.len()).filter(|i| !placed[*i]) {
+        let neighbor = (0..order.len()).min_by_key(|slot| hamming_distance(simhashes[order[*slot]], simhashes[new]));
+        match neighbor {
+            Some(slot) => order.insert(slot + 1, new),
+            None => order.push(new),
+        }
+    }
+    Some(order)
+}
+
+// Jaccard similarity of the two key sets.
+fn key_overlap(a: &[String], b: &[String]) -> f64 {
+    let shared = a.iter().filter(|key| b.contains(key)).count();
+    let total = a.len() + b.len() - shared;
+    if total == 0 {
+        return 0.0;
+    }
+    shared as f64 / total as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::manifest::{Entry, Manifest};
+
+    fn keys(keys: &[&str]) -> Vec<String> {
+        keys.iter().map(|key| key.to_string()).collect()
+    }
+
+    fn previous(entry_keys: &[&str], order: Vec<usize>) -> Manifest {
+        let entry = Entry { path: "0/0".to_string(), keys: keys(entry_keys), order, size: 0, span: (0, 0) };
+        Manifest { literals: vec![entry] }
+    }
+
+    fn units(keys: &[&str]) -> Vec<Vec<u8>> {
+        keys.iter().map(|key| format!("{}:1", key).into_bytes()).collect()
+    }
+
+    #[test]
+    fn surviving_keys_keep_their_order() {
+        let previous = previous(&["a", "b", "c"], vec![2, 0, 1]);
+        let now = ["b", "c", "a"];
+        assert_eq!(super::order(&previous, "0/0", &keys(&now), &units(&now)), Some(vec![1, 2, 0]));
+    }
+
+    #[test]
+    fn new_keys_are_placed_and_dropped_ones_skipped() {
+        let previous = previous(&["a", "b", "c", "d"], vec![3, 2, 1, 0]);
+        let now = ["a", "b", "c", "e"];
+        let order = super::order(&previous, "0/0", &keys(&now), &units(&now)).unwrap();
+        let mut sorted = order.clone();
+        sorted.sort();
+        assert_eq!(sorted, vec![0, 1, 2, 3]);
+        // c, b, a still in that order around wherever e went
+        let old: Vec<usize> = order.into_iter().filter(|i| *i != 3).collect();
+        assert_eq!(old, vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn too_few_shared_keys_or_broken_orders_give_nothing() {
+        let now = ["a", "x", "y", "z"];
+        assert_eq!(super::order(&previous(&["a", "b", "c"], vec![0, 1, 2]), "0/0", &keys(&now), &units(&now)), None);
+        let now = ["a", "b", "c"];
+        for broken in [vec![0, 1, 7], vec![0, 0, 1], vec![0, 1]] {
+            assert_eq!(super::order(&previous(&["a", "b", "c"], broken), "0/0", &keys(&now), &units(&now)), None);
+        }
+    }
+}